@@ -84,6 +84,9 @@ impl SfxHandles {
     pub const PATH_BUTTON_HOVER: &'static str = "audio/sfx/button_hover.ogg";
     pub const PATH_BUTTON_PRESS: &'static str = "audio/sfx/button_press.ogg";
     pub const PATH_STEP: &'static str = "audio/sfx/step";
+    pub const PATH_STEP_METAL: &'static str = "audio/sfx/step_metal";
+    pub const PATH_STEP_ICE: &'static str = "audio/sfx/step_ice";
+    pub const PATH_STEP_MUD: &'static str = "audio/sfx/step_mud";
 }
 
 impl FromWorld for SfxHandles {
@@ -106,6 +109,8 @@ impl FromWorld for SfxHandles {
             step_sfx.push(asset_server.load(file));
         }
         map.insert(Self::PATH_STEP.to_string(), step_sfx);
+        // There are no recordings for the other surfaces yet. Until their variations are loaded
+        // here like the ones above, their footsteps fall back to `PATH_STEP`.
 
         Self(map)
     }
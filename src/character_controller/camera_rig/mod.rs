@@ -12,7 +12,8 @@ pub fn plugin(app: &mut App) {
 #[reflect(Component)]
 pub struct RiggedCamera;
 
-// Specifies the entity that we are attached to, as well as the offset from that entity
+// Specifies the entity that we are attached to, as well as the offset from that entity in its local
+// space
 #[derive(Component, Reflect, Debug, Default)]
 #[reflect(Component)]
 pub struct TrackedEntity(pub Vec3);
@@ -24,7 +25,8 @@ pub fn track_entity(
     // There should only ever be one tracked entity and one rigged camera.
     if let Ok((tracked_entity, tracked_transform)) = query.get_single_mut() {
         if let Ok(mut camera_transform) = camera_query.get_single_mut() {
-            camera_transform.translation = tracked_entity.0 + tracked_transform.translation;
+            camera_transform.translation =
+                tracked_transform.translation + tracked_transform.rotation * tracked_entity.0;
         }
     }
 }
@@ -0,0 +1,115 @@
+//! Fall damage for characters.
+//!
+//! When a character lands, the part of its impact velocity along its gravity is turned into
+//! damage through a curve, once it exceeds a safe speed. Landing in water or on a [`SoftSurface`]
+//! deals no damage. The damage is sent as a [`DamageEvent`] credited to [`DamageSource::Fall`].
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use super::{
+    kinematic_controller::{
+        KCCFloorDetection,
+        KCCLanded,
+        KinematicCharacterController,
+    },
+    movement::{
+        character_feet,
+        Gravity,
+        ResponseCurve,
+        WaterVolume,
+    },
+    surface::SoftSurface,
+    weapon::{
+        DamageEvent,
+        DamageSource,
+    },
+    CharacterControllerSet,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(FixedUpdate, fall_damage_system.after(CharacterControllerSet::Kinematics));
+}
+
+/// Component that makes a character take damage from hard landings.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct FallDamage {
+    /// Landings slower than this along gravity, in meters per second, deal no damage.
+    pub safe_speed: f32,
+    /// Maps how much faster than `safe_speed` the character landed to the damage it takes.
+    pub damage: ResponseCurve,
+}
+
+impl Default for FallDamage {
+    fn default() -> Self {
+        Self {
+            safe_speed: 13.0,
+            damage: ResponseCurve::new([
+                Vec2::new(0.0, 0.0),
+                Vec2::new(5.0, 25.0),
+                Vec2::new(12.0, 100.0),
+            ]),
+        }
+    }
+}
+
+impl FallDamage {
+    /// The damage dealt by landing at `impact_speed` along gravity.
+    pub fn damage_for(&self, impact_speed: f32) -> f32 {
+        if impact_speed <= self.safe_speed {
+            return 0.0;
+        }
+        self.damage.sample(impact_speed - self.safe_speed)
+    }
+}
+
+/// Turns hard landings into [`DamageEvent`]s.
+///
+/// Water is checked at the position the character landed at. The submersion depth tracked by
+/// [`Swim`](super::movement::Swim) is measured before the character moves, so a fast fall into a
+/// shallow pool would still see the feet above the water on the tick it lands.
+pub fn fall_damage_system(
+    mut landed_events: EventReader<KCCLanded>,
+    mut damage_events: EventWriter<DamageEvent>,
+    query: Query<(
+        &FallDamage,
+        &Gravity,
+        &KCCFloorDetection,
+        &KinematicCharacterController,
+        &Transform,
+    )>,
+    water: Query<(&Collider, &Position, &Rotation), With<WaterVolume>>,
+    soft_surfaces: Query<(), With<SoftSurface>>,
+    collider_parents: Query<&ColliderParent>,
+) {
+    for landed in landed_events.read() {
+        let Ok((fall_damage, gravity, floor_detection, kcc, transform)) = query.get(landed.entity)
+        else {
+            continue;
+        };
+        let Some(down) = gravity.0.try_normalize() else {
+            continue;
+        };
+
+        let feet = character_feet(kcc, transform);
+        let in_water = water.iter().any(|(collider, position, rotation)| {
+            collider.contains_point(**position, **rotation, feet)
+        });
+        let on_soft_surface = floor_detection
+            .floor_entity
+            .is_some_and(|floor| SoftSurface::is_soft(floor, &soft_surfaces, &collider_parents));
+        if in_water || on_soft_surface {
+            continue;
+        }
+
+        let amount = fall_damage.damage_for(landed.impact_velocity.dot(down));
+        if amount > 0.0 {
+            damage_events.send(DamageEvent {
+                target: landed.entity,
+                amount,
+                source: DamageSource::Fall,
+            });
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Footstep audio for characters.
+//!
+//! Footsteps are driven by the distance a character covers on the ground rather than by a timer,
+//! so they always match its speed. Sprinting takes longer, louder strides and crouching shorter,
+//! quieter ones. Landings get their own louder step. The sound effects and their pitch come from
+//! the [`SurfaceMaterial`] of the floor. Every step is sent as a [`Footstep`] event, which is what
+//! plays it.
+
+use avian3d::prelude::*;
+use bevy::{
+    audio::Volume,
+    prelude::*,
+};
+
+use super::{
+    kinematic_controller::{
+        KCCFloorDetection,
+        KCCGrounded,
+        KCCLanded,
+        KinematicCharacterController,
+    },
+    movement::{
+        Crouch,
+        Slide,
+        Sprint,
+    },
+    surface::SurfaceMaterial,
+    CharacterControllerSet,
+    CurrentPlayer,
+};
+use crate::{
+    assets::SfxHandles,
+    audio::sfx::SfxCommands,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_event::<Footstep>();
+    app.add_systems(
+        FixedUpdate,
+        (
+            footstep_system.after(CharacterControllerSet::Kinematics),
+            play_footsteps.after(footstep_system).run_if(resource_exists::<SfxHandles>),
+        ),
+    );
+}
+
+/// Event sent for every footstep a character takes, including the louder step on landing.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Footstep {
+    /// The character that stepped.
+    pub entity: Entity,
+    /// The material of the floor the step was taken on.
+    pub surface: SurfaceMaterial,
+    /// The volume to play the step at.
+    pub volume: f32,
+    /// Multiplied with the surface's own playback speed.
+    pub pitch: f32,
+}
+
+/// Component that describes the footsteps of a character, and tracks the distance walked since
+/// the last one.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Footsteps {
+    /// The distance between footsteps while walking.
+    pub walk_stride: f32,
+    /// The distance between footsteps while sprinting.
+    pub sprint_stride: f32,
+    /// The distance between footsteps while crouching.
+    pub crouch_stride: f32,
+    /// The volume of footsteps while walking.
+    pub walk_volume: f32,
+    /// The volume of footsteps while sprinting.
+    pub sprint_volume: f32,
+    /// The volume of footsteps while crouching.
+    pub crouch_volume: f32,
+    /// The volume of the step played on landing.
+    pub landing_volume: f32,
+    /// Landings slower than this along the up vector don't make a sound.
+    pub min_landing_speed: f32,
+    /// The distance walked on the ground since the last footstep.
+    pub distance: f32,
+}
+
+impl Default for Footsteps {
+    fn default() -> Self {
+        Self {
+            walk_stride: 1.8,
+            sprint_stride: 2.6,
+            crouch_stride: 1.2,
+            walk_volume: 0.6,
+            sprint_volume: 0.9,
+            crouch_volume: 0.25,
+            landing_volume: 1.3,
+            min_landing_speed: 2.0,
+            distance: 0.0,
+        }
+    }
+}
+
+/// Sends footsteps for characters walking on the ground, and a louder step when they land.
+///
+/// The surface is looked up from the floor found this tick, rather than from [`FloorSurface`],
+/// which is only updated on the next tick and would still be empty on the tick the character
+/// lands.
+///
+/// [`FloorSurface`]: super::surface::FloorSurface
+pub fn footstep_system(
+    mut footstep_events: EventWriter<Footstep>,
+    mut query: Query<(
+        Entity,
+        &mut Footsteps,
+        &KinematicCharacterController,
+        &KCCGrounded,
+        Option<&KCCFloorDetection>,
+        Option<&Sprint>,
+        Option<&Crouch>,
+        Option<&Slide>,
+    )>,
+    mut landed_events: EventReader<KCCLanded>,
+    materials: Query<&SurfaceMaterial>,
+    collider_parents: Query<&ColliderParent>,
+    time: Res<Time>,
+) {
+    let floor_surface = |floor_detection: Option<&KCCFloorDetection>| {
+        floor_detection
+            .and_then(|floor_detection| floor_detection.floor_entity)
+            .map_or_else(SurfaceMaterial::default, |floor| {
+                SurfaceMaterial::of(floor, &materials, &collider_parents)
+            })
+    };
+
+    for landed in landed_events.read() {
+        let Ok((entity, mut footsteps, kcc, _, floor_detection, ..)) = query.get_mut(landed.entity)
+        else {
+            continue;
+        };
+        if -landed.impact_velocity.dot(kcc.up) < footsteps.min_landing_speed {
+            continue;
+        }
+        footsteps.distance = 0.0;
+        footstep_events.send(Footstep {
+            entity,
+            surface: floor_surface(floor_detection),
+            volume: footsteps.landing_volume,
+            pitch: 0.9,
+        });
+    }
+
+    for (entity, mut footsteps, kcc, grounded, floor_detection, sprint, crouch, slide) in &mut query
+    {
+        let sliding = slide.is_some_and(|slide| slide.sliding);
+        if !grounded.grounded || sliding {
+            continue;
+        }
+
+        footsteps.distance += kcc.velocity.reject_from(kcc.up).length() * time.delta_seconds();
+        let (stride, volume, pitch) = if crouch.is_some_and(|crouch| crouch.crouching) {
+            (footsteps.crouch_stride, footsteps.crouch_volume, 0.9)
+        } else if sprint.is_some_and(|sprint| sprint.sprinting) {
+            (footsteps.sprint_stride, footsteps.sprint_volume, 1.1)
+        } else {
+            (footsteps.walk_stride, footsteps.walk_volume, 1.0)
+        };
+        if footsteps.distance < stride {
+            continue;
+        }
+
+        footsteps.distance %= stride;
+        footstep_events.send(Footstep {
+            entity,
+            surface: floor_surface(floor_detection),
+            volume,
+            pitch,
+        });
+    }
+}
+
+/// Plays a random step sound effect for every [`Footstep`], from the surface's own recordings if
+/// they are loaded. Sound effects aren't spatial yet, so only the current player's own steps are
+/// played, rather than every character's at full volume.
+fn play_footsteps(
+    mut commands: Commands,
+    mut footstep_events: EventReader<Footstep>,
+    current_player: Query<(), With<CurrentPlayer>>,
+    sfx: Res<SfxHandles>,
+) {
+    for footstep in footstep_events.read() {
+        if !current_player.contains(footstep.entity) {
+            continue;
+        }
+        let key = footstep.surface.footstep_sfx();
+        let key = if sfx.contains_key(key) { key } else { SfxHandles::PATH_STEP };
+        commands.play_sfx_with_settings(
+            key,
+            PlaybackSettings::DESPAWN
+                .with_volume(Volume::new(footstep.volume))
+                .with_speed(footstep.surface.footstep_speed() * footstep.pitch),
+        );
+    }
+}
@@ -13,6 +13,9 @@ pub fn input_map() -> InputMap<PlayerActions> {
     map.insert_dual_axis(PlayerActions::Movement, KeyboardVirtualDPad::WASD);
     map.insert_dual_axis(PlayerActions::Camera, MouseMove::default());
     map.insert(PlayerActions::Jump, KeyCode::Space);
+    map.insert(PlayerActions::Crouch, KeyCode::ControlLeft);
+    map.insert(PlayerActions::Sprint, KeyCode::ShiftLeft);
+    map.insert(PlayerActions::Dash, KeyCode::KeyQ);
     map.insert(PlayerActions::Reload, KeyCode::KeyR);
     map
 }
@@ -24,6 +27,9 @@ pub enum PlayerActions {
     Movement,
     Camera,
     Jump,
+    Crouch,
+    Sprint,
+    Dash,
     PrimaryAttack,
     Reload,
 }
@@ -34,6 +40,9 @@ impl Actionlike for PlayerActions {
             PlayerActions::Movement => InputControlKind::DualAxis,
             PlayerActions::Camera => InputControlKind::DualAxis,
             PlayerActions::Jump => InputControlKind::Button,
+            PlayerActions::Crouch => InputControlKind::Button,
+            PlayerActions::Sprint => InputControlKind::Button,
+            PlayerActions::Dash => InputControlKind::Button,
             PlayerActions::PrimaryAttack => InputControlKind::Button,
             PlayerActions::Reload => InputControlKind::Button,
         }
@@ -7,35 +7,62 @@
 use avian3d::prelude::*;
 use bevy::prelude::*;
 
+use super::CharacterControllerSet;
+
 mod movement;
+mod platform;
 
 pub(super) fn plugin(app: &mut App) {
+    app.add_event::<KCCLanded>();
+    app.add_event::<KCCLeftGround>();
+    app.add_event::<KCCDepenetrationFailed>();
+    app.add_event::<KCCCollision>();
+    app.add_event::<KCCImpulse>();
+    // Runs on the fixed timestep right after input, so the simulation doesn't depend on the frame
+    // rate. Rendering is smoothed out by transform interpolation.
     app.add_systems(
-        PostUpdate,
+        FixedUpdate,
         (
-            movement::collide_and_slide_system,
+            apply_impulses,
             update_kinematic_character_controller,
+            platform::move_with_floor,
+            movement::collide_and_slide_system,
             update_kinematic_floor,
             floor_snap,
         )
-            .chain(),
+            .chain()
+            .in_set(CharacterControllerSet::Kinematics),
     );
 }
 
+/// Returns the rigid body the collider `entity` belongs to. Colliders may be children of the rigid
+/// body that actually moves, a collider without a parent is its own body.
+pub fn collider_body(entity: Entity, collider_parents: &Query<&ColliderParent>) -> Entity {
+    collider_parents.get(entity).map_or(entity, ColliderParent::get)
+}
+
 /// A component that represents the core logic of a kinematic character controller.
 /// This component has a dedicated system that updates its internal state and calls the movement
 /// basis.
 #[derive(Component, Reflect, Debug)]
 #[reflect(Component)]
 pub struct KinematicCharacterController {
-    /// The velocity we had last tick.
+    /// The velocity we had before collisions were resolved this tick.
     pub prev_velocity: Vec3,
     /// The velocity we have this tick.
     pub velocity: Vec3,
     /// The up vector of the character.
     pub up: Vec3,
-    /// How many times the collider will "bounce" off of surfaces.
+    /// How many times the collider will "bounce" off of surfaces in a single sweep.
     pub bounces: u32,
+    /// How many sweeps ran out of bounces and had the rest of their motion dropped. This is a
+    /// diagnostic counter, a steadily rising value means `bounces` is too low for the level.
+    pub truncated_moves: u32,
+    /// The tallest obstacle, such as a stair or a curb, that the character can step onto without
+    /// jumping. Set to `0.0` to disable stepping.
+    pub max_step_height: f32,
+    /// How many rounds of contact resolution depenetration may run each tick before giving up.
+    pub max_depenetration_iterations: u32,
     /// The collider that represents the shape of this character.
     #[reflect(ignore)]
     pub collider: Collider,
@@ -48,6 +75,9 @@ impl Default for KinematicCharacterController {
             velocity: Vec3::ZERO,
             up: Vec3::Y,
             bounces: 4,
+            truncated_moves: 0,
+            max_step_height: 0.3,
+            max_depenetration_iterations: 8,
             collider: Collider::capsule(0.4, 0.8),
         }
     }
@@ -62,6 +92,9 @@ pub struct KCCGrounded {
     pub grounded: bool,
     /// Was this character grounded last tick?
     pub prev_grounded: bool,
+    /// Seconds left during which this character can't become grounded. This is set when an
+    /// impulse launches the character, so the floor it is leaving doesn't catch it again.
+    pub unground_time: f32,
 }
 
 /// Component that represents the floor detection of a kinematic character controller.
@@ -83,6 +116,18 @@ pub struct KCCFloorDetection {
     pub floor_distance: f32,
     /// How far from the floor this character can be before it is considered not grounded.
     pub max_floor_distance: f32,
+    /// The steepest angle, in radians, between the floor normal and the character's up vector that
+    /// still counts as walkable ground. Anything steeper is treated as a wall that we slide down.
+    pub max_slope_angle: f32,
+    /// The normal of the ledge the character stepped up onto this tick, if any.
+    /// This is written by the collide and slide pass so that the character is considered grounded
+    /// right after stepping, even before the floor shapecast catches up.
+    pub step_normal: Option<Vec3>,
+    /// The collider we are currently standing on, if any.
+    pub floor_entity: Option<Entity>,
+    /// The velocity of the floor at the character's position. Characters are carried along by this
+    /// velocity while grounded, and keep it as momentum when they leave the floor.
+    pub floor_velocity: Vec3,
 }
 
 impl Default for KCCFloorDetection {
@@ -94,18 +139,138 @@ impl Default for KCCFloorDetection {
             floor_collider: Collider::capsule(0.4, 0.8),
             floor_distance: 0.0,
             max_floor_distance: 0.05,
+            max_slope_angle: 45.0_f32.to_radians(),
+            step_normal: None,
+            floor_entity: None,
+            floor_velocity: Vec3::ZERO,
         }
     }
 }
 
+impl KCCFloorDetection {
+    /// Returns `true` if a surface with the given `normal` is shallow enough to stand on.
+    pub fn is_walkable(&self, normal: Vec3, up: Vec3) -> bool {
+        normal != Vec3::ZERO && normal.angle_between(up) <= self.max_slope_angle
+    }
+}
+
+/// Component that records the wall a kinematic character controller is touching.
+/// It is filled in from the surfaces the collide and slide pass bumps into, so a character only
+/// counts as touching a wall while it is moving into it.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct KCCWallDetection {
+    /// The normal of the wall we touched this tick, pointing towards the character.
+    pub wall_normal: Option<Vec3>,
+    /// The collider of the wall we touched this tick.
+    pub wall_entity: Option<Entity>,
+    /// Surfaces whose normal has an absolute dot product with the up vector below this count as
+    /// walls.
+    pub wall_threshold: f32,
+}
+
+impl Default for KCCWallDetection {
+    fn default() -> Self {
+        Self { wall_normal: None, wall_entity: None, wall_threshold: 0.3 }
+    }
+}
+
+/// Event sent when a character with [`KCCGrounded`] lands on walkable ground.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct KCCLanded {
+    /// The character that landed.
+    pub entity: Entity,
+    /// The velocity the character had when it hit the ground.
+    pub impact_velocity: Vec3,
+}
+
+/// Event sent when a character with [`KCCGrounded`] stops being grounded, be it from jumping,
+/// walking off a ledge or sliding onto a surface that is too steep.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct KCCLeftGround {
+    /// The character that left the ground.
+    pub entity: Entity,
+    /// The velocity the character had when it left the ground.
+    pub velocity: Vec3,
+}
+
+/// A component that when added to the controller lets it push dynamic bodies it walks into.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct KCCPush {
+    /// The mass of the character, used to scale the impulses applied to the bodies it hits.
+    pub mass: f32,
+    /// Multiplier for the applied impulses, to make pushing feel stronger or weaker.
+    pub strength: f32,
+}
+
+impl Default for KCCPush {
+    fn default() -> Self {
+        Self { mass: 80.0, strength: 1.0 }
+    }
+}
+
+/// Marker component for dynamic bodies that characters should not push around.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
+pub struct KCCUnpushable;
+
+/// Event sent for every surface a character bumps into while moving.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct KCCCollision {
+    /// The character that bumped into something.
+    pub entity: Entity,
+    /// The collider that was hit.
+    pub other: Entity,
+    /// The normal of the hit surface, pointing towards the character.
+    pub normal: Vec3,
+    /// The point of contact on the hit surface.
+    pub point: Vec3,
+    /// How fast the character was moving into the surface when it hit.
+    pub impact_speed: f32,
+}
+
+/// How long, in seconds, an upward impulse keeps a character from being grounded.
+const IMPULSE_UNGROUND_TIME: f32 = 0.1;
+
+/// Event that changes the velocity of a character, for knockback, explosions or jump pads.
+/// Impulses are applied at the start of the next kinematics step, in the order they were sent.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct KCCImpulse {
+    /// The character to push.
+    pub entity: Entity,
+    /// The change in velocity, in meters per second, before [`KCCKnockbackResistance`].
+    pub impulse: Vec3,
+}
+
+/// A component that when added to the controller scales down the impulses it receives.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
+pub struct KCCKnockbackResistance(
+    /// The fraction of every impulse that is ignored, `0.0` takes the full impulse and `1.0` makes
+    /// the character immovable.
+    pub f32,
+);
+
+/// Event sent when a character is still stuck inside geometry after depenetration used up its
+/// iteration budget.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct KCCDepenetrationFailed {
+    /// The character that is stuck.
+    pub entity: Entity,
+    /// The deepest penetration that was left unresolved.
+    pub penetration: f32,
+}
+
 /// A component that when added to the controller enables snapping to the floor.
 /// This component requires the [`KCCFloorDetection`] and the [`KCCGrounded`] components to be
 /// present on the same entity.
 #[derive(Component, Reflect, Debug, Default)]
 #[reflect(Component)]
 pub struct KCCFloorSnap;
+
 /// Function that updates the kinematic character controller's internal state. Currently, this only
-/// updates the previous velocity.
+/// updates the previous velocity, so it runs before collisions are resolved.
 pub fn update_kinematic_character_controller(
     mut query: Query<(&mut KinematicCharacterController, &mut LinearVelocity)>,
 ) {
@@ -115,33 +280,104 @@ pub fn update_kinematic_character_controller(
     }
 }
 
+/// Applies the queued [`KCCImpulse`]s to their characters. An impulse that pushes a character
+/// upwards ungrounds it, so it is launched off the floor rather than snapped back to it.
+pub fn apply_impulses(
+    mut impulses: EventReader<KCCImpulse>,
+    mut query: Query<(
+        &mut KinematicCharacterController,
+        Option<&mut KCCGrounded>,
+        Option<&KCCKnockbackResistance>,
+    )>,
+) {
+    for impulse in impulses.read() {
+        let Ok((mut controller, grounded, resistance)) = query.get_mut(impulse.entity) else {
+            continue;
+        };
+        let scale = 1.0 - resistance.map_or(0.0, |resistance| resistance.0.clamp(0.0, 1.0));
+        let velocity_change = impulse.impulse * scale;
+        controller.velocity += velocity_change;
+
+        if let Some(mut grounded) = grounded.filter(|_| velocity_change.dot(controller.up) > 0.0) {
+            grounded.unground_time = IMPULSE_UNGROUND_TIME;
+        }
+    }
+}
+
+/// Function that runs the floor shapecast and updates the grounded state of each character.
+/// Sends [`KCCLanded`] and [`KCCLeftGround`] events whenever the grounded state changes.
 pub fn update_kinematic_floor(
-    mut query: Query<(&mut KCCFloorDetection, &Transform, Option<&mut KCCGrounded>, Entity)>,
+    mut query: Query<(
+        &mut KCCFloorDetection,
+        &Transform,
+        Option<&mut KCCGrounded>,
+        Option<&KinematicCharacterController>,
+        Entity,
+    )>,
     spatial_query: SpatialQuery,
+    mut landed_events: EventWriter<KCCLanded>,
+    mut left_ground_events: EventWriter<KCCLeftGround>,
+    time: Res<Time>,
 ) {
-    for (mut floor_detection, transform, mut grounded, entity) in query.iter_mut() {
+    for (mut floor_detection, transform, mut grounded, controller, entity) in query.iter_mut() {
         floor_detection.prev_floor_normal = floor_detection.floor_normal;
-        if let Some(grounded) = grounded.as_mut() {
-            grounded.prev_grounded = grounded.grounded;
-        }
+        let step_normal = floor_detection.step_normal.take();
 
-        let Some(cast) = spatial_query.cast_shape(
+        let cast = spatial_query.cast_shape(
             &floor_detection.floor_collider,
             transform.translation,
-            Quat::IDENTITY,
+            transform.rotation,
             Dir3::new_unchecked(floor_detection.ground_direction.normalize()),
             floor_detection.max_floor_distance,
             false,
             &SpatialQueryFilter::default().with_excluded_entities([entity]),
-        ) else {
-            // Nothing was hit, move on.
+        );
+
+        let up = controller.map_or(-floor_detection.ground_direction, |controller| controller.up);
+        let is_grounded = match (cast, step_normal) {
+            (Some(cast), _) => {
+                floor_detection.floor_normal = cast.normal1;
+                floor_detection.floor_distance = cast.time_of_impact;
+                // Surfaces that are too steep are not ground, we slide down them instead.
+                let walkable = floor_detection.is_walkable(cast.normal1, up);
+                floor_detection.floor_entity = walkable.then_some(cast.entity);
+                walkable
+            }
+            // We stepped up onto a ledge this tick, so we're standing on it even if the cast
+            // missed.
+            (None, Some(step_normal)) => {
+                floor_detection.floor_normal = step_normal;
+                floor_detection.floor_distance = 0.0;
+                // Keep the floor we stepped from, clearing it would make us leave a moving
+                // platform and gain its momentum while still standing on it.
+                true
+            }
+            // Nothing was hit, we're in the air.
+            (None, None) => {
+                floor_detection.floor_normal = Vec3::ZERO;
+                floor_detection.floor_distance = 0.0;
+                floor_detection.floor_entity = None;
+                false
+            }
+        };
+
+        let Some(grounded) = grounded.as_mut() else {
             continue;
         };
+        grounded.prev_grounded = grounded.grounded;
+        grounded.grounded = is_grounded && grounded.unground_time <= 0.0;
+        grounded.unground_time = (grounded.unground_time - time.delta_seconds()).max(0.0);
 
-        floor_detection.floor_normal = cast.normal1;
-        floor_detection.floor_distance = cast.time_of_impact;
-        if let Some(grounded) = grounded.as_mut() {
-            grounded.grounded = true;
+        // The velocity from before collisions were resolved, so landing impacts aren't lost.
+        let velocity = controller.map_or(Vec3::ZERO, |controller| controller.prev_velocity);
+        match (grounded.prev_grounded, grounded.grounded) {
+            (false, true) => {
+                landed_events.send(KCCLanded { entity, impact_velocity: velocity });
+            }
+            (true, false) => {
+                left_ground_events.send(KCCLeftGround { entity, velocity });
+            }
+            _ => {}
         }
     }
 }
@@ -157,10 +393,11 @@ pub fn floor_snap(
 ) {
     for (mut transform, floor_detection, grounded, _, controller) in query.iter_mut() {
         if (grounded.grounded || grounded.prev_grounded)
-            && controller.velocity.y <= 0.0
+            && controller.velocity.dot(controller.up) <= 0.0
             && floor_detection.floor_distance < 0.01
         {
-            transform.translation.y -= floor_detection.floor_distance - 0.001;
+            transform.translation += floor_detection.ground_direction.normalize()
+                * (floor_detection.floor_distance - 0.001);
         }
     }
 }
@@ -10,8 +10,10 @@
 //!   entities with a `KinematicCharacterController` component.
 //! - `collide_and_slide`: A function that implements the core logic for collision detection and
 //!   sliding, based on the Source engine's approach.
-//! - `depenetrate`: A function that implements basic depenetration logic. This is ran after sliding
-//!   to prevent the character from penetrating the surface.
+//! - `try_step_up`: A function that lets the character climb stairs and curbs by sweeping up,
+//!   forward and back down when it is blocked horizontally.
+//! - `depenetrate`: A function that iteratively resolves every contact the character overlaps. This
+//!   is ran after sliding to prevent the character from penetrating the surface.
 //! ## Usage
 //!
 //! To use this module, add the `collide_and_slide_system` to your game's schedule
@@ -23,140 +25,526 @@
 //! This module relies on the `avian3d` crate for physics operations and interactions.
 
 use avian3d::{
+    collision::contact_query,
     math::AdjustPrecision,
     prelude::*,
 };
 use bevy::prelude::*;
 
-use super::KinematicCharacterController;
+use super::{
+    collider_body,
+    KCCCollision,
+    KCCDepenetrationFailed,
+    KCCFloorDetection,
+    KCCGrounded,
+    KCCPush,
+    KCCUnpushable,
+    KCCWallDetection,
+    KinematicCharacterController,
+};
+
+/// The steepest walkable slope for characters without a [`KCCFloorDetection`] component.
+const DEFAULT_MAX_SLOPE_ANGLE: f32 = std::f32::consts::FRAC_PI_4;
 
 /// Handles collision detection and sliding for kinematic character controllers.
 ///
 /// # Arguments
 /// * `query` - Query for character controllers
+/// * `colliders` - Query for the colliders characters may overlap, used for depenetration
+/// * `collider_parents` - Query for finding the rigid body a hit collider belongs to
+/// * `bodies` - Query for the dynamic bodies characters may push
 /// * `spatial_query` - Spatial query system for collision detection
+/// * `collision_events` - Event writer for every surface a character bumps into
+/// * `depenetration_failed` - Event writer for characters that could not be depenetrated
 /// * `time` - Time resource for delta time calculations
 pub fn collide_and_slide_system(
-    mut query: Query<(&mut Transform, Entity, &mut KinematicCharacterController), With<RigidBody>>,
+    mut query: Query<
+        (
+            &mut Transform,
+            Entity,
+            &mut KinematicCharacterController,
+            Option<&KCCGrounded>,
+            Option<&mut KCCFloorDetection>,
+            Option<&mut KCCWallDetection>,
+            Option<&KCCPush>,
+        ),
+        With<RigidBody>,
+    >,
+    colliders: Query<(&Collider, &Position, &Rotation)>,
+    collider_parents: Query<&ColliderParent>,
+    mut bodies: Query<
+        (&RigidBody, &Mass, &CenterOfMass, &Position, &Rotation, &mut ExternalImpulse),
+        Without<KCCUnpushable>,
+    >,
     mut spatial_query: SpatialQuery,
+    mut collision_events: EventWriter<KCCCollision>,
+    mut depenetration_failed: EventWriter<KCCDepenetrationFailed>,
     time: Res<Time>,
 ) {
-    for (mut transform, entity, mut controller) in &mut query {
+    let mut collisions = Vec::new();
+    for (mut transform, entity, mut controller, grounded, floor_detection, wall_detection, push) in
+        &mut query
+    {
         let filter = SpatialQueryFilter::default().with_excluded_entities([entity]);
+        let grounded = grounded.is_some_and(|grounded| grounded.grounded);
+        let max_slope_angle = floor_detection
+            .as_ref()
+            .map_or(DEFAULT_MAX_SLOPE_ANGLE, |floor_detection| floor_detection.max_slope_angle);
 
-        collide_and_slide(&mut spatial_query, &filter, &mut controller, &mut transform, &time);
+        let step_normal = collide_and_slide(
+            &mut spatial_query,
+            &filter,
+            entity,
+            &mut controller,
+            &mut transform,
+            grounded,
+            max_slope_angle,
+            &mut collisions,
+            &time,
+        );
+        if let Some(mut wall_detection) = wall_detection {
+            let up = controller.up;
+            let wall = collisions
+                .iter()
+                .rev()
+                .find(|collision| collision.normal.dot(up).abs() < wall_detection.wall_threshold);
+            wall_detection.wall_normal = wall.map(|collision| collision.normal);
+            wall_detection.wall_entity = wall.map(|collision| collision.other);
+        }
+        if let Some(push) = push {
+            push_bodies(&collisions, push, &collider_parents, &mut bodies);
+        }
+        collision_events.send_batch(collisions.drain(..));
+        if let (Some(step_normal), Some(mut floor_detection)) = (step_normal, floor_detection) {
+            floor_detection.step_normal = Some(step_normal);
+        }
 
-        depenetrate(&mut spatial_query, &filter, &controller.collider, &mut transform);
+        if let Some(penetration) = depenetrate(
+            &mut spatial_query,
+            &filter,
+            &colliders,
+            &controller.collider,
+            &mut transform,
+            controller.max_depenetration_iterations,
+        ) {
+            // This fires every tick while stuck, so keep it out of the default log level.
+            debug!("Failed to depenetrate {entity}, still {penetration} units inside geometry.");
+            depenetration_failed.send(KCCDepenetrationFailed { entity, penetration });
+        }
     }
 }
 
 /// Implements collision detection and sliding for a kinematic character controller.
 ///
+/// Moves that are longer than the collider is thick are split into sub-steps, so fast characters
+/// can't skip past thin geometry. Each sub-step may bounce off of at most
+/// [`KinematicCharacterController::bounces`] surfaces, if it runs out of bounces the rest of the
+/// sub-step's motion is dropped and counted in [`KinematicCharacterController::truncated_moves`].
+///
 /// # Arguments
 /// * `spatial_query` - Spatial query system for collision detection
 /// * `filter` - Filter to exclude specific entities from collision checks
+/// * `entity` - The character entity, used to fill in collision reports
 /// * `controller` - Kinematic character controller to update
 /// * `transform` - Transform of the character to update
+/// * `grounded` - Whether the character was grounded last tick, stepping is only allowed if so
+/// * `max_slope_angle` - The steepest slope, in radians, that the character can walk up
+/// * `collisions` - Every surface the character bumps into is pushed here
 /// * `time` - Time resource for delta time calculations
+///
+/// Returns the normal of the ledge the character stepped onto, if it stepped this tick.
 fn collide_and_slide(
     spatial_query: &mut spatial_query::SpatialQuery,
     filter: &spatial_query::SpatialQueryFilter,
+    entity: Entity,
     kinematic_controller: &mut KinematicCharacterController,
     transform: &mut Transform,
+    grounded: bool,
+    max_slope_angle: f32,
+    collisions: &mut Vec<KCCCollision>,
     time: &Res<Time>,
-) {
+) -> Option<Vec3> {
     const EPSILON: f32 = 0.01; // Small padding value to prevent precision issues
-    const MAX_BUMPS: u32 = 4; // Maximum number of collision iterations
+    const MAX_SUBSTEPS: u32 = 16; // Upper bound on sub-steps for extremely fast moves
     let delta_seconds = time.delta_seconds_f64().adjust_precision();
-    let mut velocity = kinematic_controller.velocity * delta_seconds;
-    let mut planes = Vec::new();
+    let mut step_normal = None;
+    let up = Dir3::new(kinematic_controller.up).unwrap_or(Dir3::Y);
+    let min_walkable_dot = max_slope_angle.cos();
 
-    for bump in 0..MAX_BUMPS {
-        if velocity.length_squared() == 0.0 {
-            break;
-        }
+    // Never sweep further than the collider is thick in a single sub-step.
+    let aabb = kinematic_controller.collider.aabb(Vec3::ZERO, Quat::IDENTITY);
+    let thickness = ((aabb.max - aabb.min).min_element() * 0.5).max(EPSILON);
+    let distance = (kinematic_controller.velocity * delta_seconds).length();
+    let substeps = if distance.is_finite() {
+        ((distance / thickness).ceil() as u32).clamp(1, MAX_SUBSTEPS)
+    } else {
+        1
+    };
+    let substep_seconds = delta_seconds / substeps as f32;
+    // How far the feet are below the collider's center, used to tell how tall an obstacle is. The
+    // collider turns with the character, so its bounds must be too.
+    let rotated_aabb = kinematic_controller.collider.aabb(Vec3::ZERO, transform.rotation);
+    let half_height = ((rotated_aabb.max - rotated_aabb.min) * 0.5).dot(up.abs());
+
+    for _ in 0..substeps {
+        let mut velocity = kinematic_controller.velocity * substep_seconds;
+        let mut planes = Vec::new();
+        let mut resolved = false;
 
-        // Handle edge cases
-        if velocity.is_nan() {
-            velocity = Vec3::ZERO;
-            break;
+        for bump in 0..kinematic_controller.bounces {
+            if velocity.length_squared() == 0.0 {
+                resolved = true;
+                break;
+            }
+
+            // Handle edge cases
+            if velocity.is_nan() {
+                kinematic_controller.velocity = Vec3::ZERO;
+                resolved = true;
+                break;
+            }
+
+            if !velocity.is_finite() {
+                error!(
+                    "Failed to run `collide_and_slide`: velocity is not finite, but `{velocity:?}`. Escaped after {bump} bumps.",
+                );
+                kinematic_controller.velocity = Vec3::ZERO;
+                resolved = true;
+                break;
+            }
+
+            let (velocity_normalized, length) = Dir3::new_and_length(velocity).unwrap();
+            let Some(hit) = spatial_query.cast_shape(
+                &kinematic_controller.collider,
+                transform.translation,
+                transform.rotation,
+                velocity_normalized,
+                length,
+                false,
+                filter,
+            ) else {
+                // Nothing in the way, finish the move.
+                transform.translation += velocity;
+                resolved = true;
+                break;
+            };
+
+            collisions.push(KCCCollision {
+                entity,
+                other: hit.entity,
+                normal: hit.normal1,
+                point: hit.point1,
+                impact_speed: -kinematic_controller.velocity.dot(hit.normal1),
+            });
+
+            // Move to the safe distance minus padding
+            let safe_movement = *velocity_normalized * (hit.time_of_impact - EPSILON).max(0.0);
+            transform.translation += safe_movement;
+            velocity -= safe_movement;
+
+            // Try to climb over the obstacle if we hit it low enough for it to be a stair or a
+            // curb. The capsule's rounded bottom meets step edges at an angle, so the normal alone
+            // can't tell a step from a wall.
+            let feet = transform.translation - *up * half_height;
+            let hit_height = (hit.point1 - feet).dot(*up);
+            if grounded
+                && kinematic_controller.max_step_height > 0.0
+                && hit_height <= kinematic_controller.max_step_height
+                && hit.normal1.dot(*up) < min_walkable_dot
+            {
+                if let Some((stepped_translation, normal)) = try_step_up(
+                    spatial_query,
+                    filter,
+                    &kinematic_controller.collider,
+                    transform,
+                    velocity,
+                    up,
+                    kinematic_controller.max_step_height,
+                    min_walkable_dot,
+                ) {
+                    // Only the horizontal part of the motion is consumed by the step.
+                    let horizontal_step =
+                        (stepped_translation - transform.translation).reject_from(*up);
+                    transform.translation = stepped_translation;
+                    velocity -= horizontal_step;
+                    step_normal = Some(normal);
+                    continue;
+                }
+            }
+
+            // Clip both the remaining motion and the velocity, so the character keeps sliding
+            // along the surface at the same speed next tick.
+            planes.push(hit.normal1);
+            let rising = !grounded && kinematic_controller.velocity.dot(*up) > 0.0;
+            velocity = clip_to_planes(velocity, &planes, up, min_walkable_dot, rising);
+            kinematic_controller.velocity = clip_to_planes(
+                kinematic_controller.velocity,
+                &planes,
+                up,
+                min_walkable_dot,
+                rising,
+            );
         }
 
-        if !velocity.is_finite() {
-            error!(
-                "Failed to run `collide_and_slide`: velocity is not finite, but `{velocity:?}`. Escaped after {bump} bumps.",
+        if !resolved {
+            // We ran out of bounces, drop the rest of the move rather than forcing it through.
+            kinematic_controller.truncated_moves += 1;
+            debug!(
+                "Truncated `collide_and_slide` for {entity} after {} bounces, dropped {velocity:?}.",
+                kinematic_controller.bounces
             );
-            velocity = Vec3::ZERO;
-            break;
         }
+    }
+
+    step_normal
+}
+
+/// Removes the parts of `velocity` that would push into any of the `planes`.
+///
+/// The newest plane is clipped against first. When sliding along multiple planes, the velocity is
+/// constrained to the creases between them. Steep slopes, which are any planes not walkable
+/// according to `min_walkable_dot`, can't be walked up, so sliding along them is not allowed to
+/// add upwards velocity, unless `rising` is set because the character is already moving up on its
+/// own, such as during a jump. Upwards velocity we had before the slide is kept either way, and
+/// gravity will still pull us down along the slope.
+fn clip_to_planes(
+    velocity: Vec3,
+    planes: &[Vec3],
+    up: Dir3,
+    min_walkable_dot: f32,
+    rising: bool,
+) -> Vec3 {
+    let Some(&normal) = planes.last() else {
+        return velocity;
+    };
+    let up_speed = velocity.dot(*up);
+    let mut velocity = velocity.reject_from(normal);
+
+    let up_dot = normal.dot(*up);
+    if !rising && up_dot > 0.0 && up_dot < min_walkable_dot {
+        let added_up_speed = velocity.dot(*up) - up_speed.max(0.0);
+        if added_up_speed > 0.0 {
+            velocity -= *up * added_up_speed;
+        }
+    }
+
+    // Handle sliding along multiple planes
+    if planes.len() > 1 {
+        for (plane, next_plane) in
+            planes.iter().zip(planes.iter().cycle().skip(1)).take(planes.len())
+        {
+            let crease = plane.cross(*next_plane);
+            if crease.length_squared() <= f32::EPSILON {
+                // Hitting the same plane twice changes nothing, but opposing planes leave no room
+                // to slide.
+                if plane.dot(*next_plane) < 0.0 {
+                    return Vec3::ZERO;
+                }
+                continue;
+            }
+            velocity = velocity.project_onto(crease);
+        }
+    }
+
+    velocity
+}
 
-        let (velocity_normalized, length) = Dir3::new_and_length(velocity).unwrap();
-        let hit = spatial_query.cast_shape(
-            &kinematic_controller.collider,
+/// Applies impulses to the dynamic bodies a character bumped into.
+///
+/// The impulse is the momentum the character would transfer in a perfectly inelastic collision, so
+/// light crates are shoved aside while heavy ones barely budge.
+///
+/// # Arguments
+/// * `collisions` - The collisions the character had this tick
+/// * `push` - The push settings of the character
+/// * `collider_parents` - Query for finding the rigid body a hit collider belongs to
+/// * `bodies` - Query for the dynamic bodies that can be pushed
+fn push_bodies(
+    collisions: &[KCCCollision],
+    push: &KCCPush,
+    collider_parents: &Query<&ColliderParent>,
+    bodies: &mut Query<
+        (&RigidBody, &Mass, &CenterOfMass, &Position, &Rotation, &mut ExternalImpulse),
+        Without<KCCUnpushable>,
+    >,
+) {
+    for collision in collisions {
+        if collision.impact_speed <= 0.0 {
+            continue;
+        }
+        let body = collider_body(collision.other, collider_parents);
+        let Ok((rigid_body, mass, center_of_mass, position, rotation, mut impulse)) =
+            bodies.get_mut(body)
+        else {
+            continue;
+        };
+        if !rigid_body.is_dynamic() {
+            continue;
+        }
+
+        let effective_mass = push.mass * mass.0 / (push.mass + mass.0);
+        let world_center_of_mass = position.0 + rotation.0 * center_of_mass.0;
+        impulse.apply_impulse_at_point(
+            -collision.normal * collision.impact_speed * effective_mass * push.strength,
+            collision.point,
+            world_center_of_mass,
+        );
+    }
+}
+
+/// Attempts to step over an obstacle that blocked horizontal movement.
+///
+/// The collider is swept up by at most `max_step_height`, then forward along the horizontal part of
+/// `motion`, and finally back down onto the obstacle. The step only succeeds if the forward sweep
+/// made progress and the downward sweep lands on a walkable surface.
+///
+/// # Arguments
+/// * `spatial_query` - Spatial query system for collision detection
+/// * `filter` - Filter to exclude specific entities from collision checks
+/// * `collider` - Collider of the character
+/// * `transform` - Transform of the character before stepping
+/// * `motion` - The remaining motion for this tick
+/// * `up` - The up direction of the character
+/// * `max_step_height` - The tallest obstacle that can be stepped onto
+/// * `min_walkable_dot` - The smallest dot product between a floor normal and `up` that is walkable
+///
+/// Returns the translation after stepping and the normal of the surface stepped onto.
+fn try_step_up(
+    spatial_query: &spatial_query::SpatialQuery,
+    filter: &spatial_query::SpatialQueryFilter,
+    collider: &Collider,
+    transform: &Transform,
+    motion: Vec3,
+    up: Dir3,
+    max_step_height: f32,
+    min_walkable_dot: f32,
+) -> Option<(Vec3, Vec3)> {
+    const EPSILON: f32 = 0.01;
+
+    let (forward, forward_length) = Dir3::new_and_length(motion.reject_from(*up)).ok()?;
+
+    // Sweep up, stopping short of any ceiling.
+    let step_height = spatial_query
+        .cast_shape(
+            collider,
             transform.translation,
             transform.rotation,
-            velocity_normalized,
-            length,
+            up,
+            max_step_height,
             false,
             filter,
-        );
+        )
+        .map_or(max_step_height, |hit| hit.time_of_impact - EPSILON);
+    if step_height <= EPSILON {
+        return None;
+    }
+    let raised = transform.translation + *up * step_height;
 
-        if let Some(hit) = hit {
-            // Move to the safe distance minus padding
-            let safe_movement = velocity * (hit.time_of_impact - EPSILON).max(0.0);
-            transform.translation += safe_movement;
+    // Sweep forward from the raised position. If we can't move, the obstacle is too tall.
+    let advance = spatial_query
+        .cast_shape(collider, raised, transform.rotation, forward, forward_length, false, filter)
+        .map_or(forward_length, |hit| hit.time_of_impact - EPSILON);
+    if advance <= EPSILON {
+        return None;
+    }
+    let advanced = raised + *forward * advance;
 
-            // Update velocity
-            velocity -= safe_movement;
-            planes.push(hit.normal1);
-            velocity = velocity.reject_from(hit.normal1);
-
-            // Handle sliding along multiple planes
-            if planes.len() > 1 {
-                for (plane, next_plane) in
-                    planes.iter().zip(planes.iter().cycle().skip(1)).take(planes.len())
-                {
-                    let crease = plane.cross(*next_plane);
-                    velocity = velocity.project_onto(crease);
-                }
-            }
-        } else {
-            break;
-        }
+    // Sweep back down onto the top of the obstacle.
+    let floor = spatial_query.cast_shape(
+        collider,
+        advanced,
+        transform.rotation,
+        -up,
+        step_height,
+        false,
+        filter,
+    )?;
+    if floor.time_of_impact <= 0.0 || floor.normal1.dot(*up) < min_walkable_dot {
+        return None;
     }
 
-    // Update the kinematic controller and transform
-    kinematic_controller.velocity = velocity / delta_seconds;
-    transform.translation += velocity;
+    Some((advanced - *up * (floor.time_of_impact - EPSILON).max(0.0), floor.normal1))
 }
 
-/// Performs depenetration for a kinematic character controller.
+/// Performs iterative depenetration for a kinematic character controller.
+///
+/// Every collider overlapping the character is gathered, and all of their contacts are resolved
+/// together so that corners and sloped ceilings don't push the character back and forth between
+/// surfaces. This repeats until nothing overlaps or the iteration budget runs out.
 ///
 /// # Arguments
 /// * `spatial_query` - Spatial query system for collision detection
 /// * `filter` - Filter to exclude specific entities from collision checks
+/// * `colliders` - Query for the colliders the character may overlap
 /// * `collider` - Collider of the character
 /// * `transform` - Transform of the character to update
+/// * `max_iterations` - How many times to gather and resolve contacts before giving up
+///
+/// Returns the deepest penetration left unresolved, or `None` if the character is free.
 fn depenetrate(
     spatial_query: &mut spatial_query::SpatialQuery,
     filter: &spatial_query::SpatialQueryFilter,
+    colliders: &Query<(&Collider, &Position, &Rotation)>,
     collider: &Collider,
     transform: &mut Transform,
-) {
+    max_iterations: u32,
+) -> Option<f32> {
     const EPSILON: f32 = 0.001;
 
-    let hit = spatial_query.cast_shape(
-        collider,
-        transform.translation,
-        transform.rotation,
-        Dir3::NEG_Y,
-        0.0,
-        false,
-        filter,
-    );
+    for _ in 0..max_iterations {
+        let contacts = penetrating_contacts(spatial_query, filter, colliders, collider, transform);
+        if contacts.is_empty() {
+            return None;
+        }
 
-    if let Some(hit) = hit {
-        let push_out_distance = hit.time_of_impact + EPSILON;
-        transform.translation += hit.normal1 * push_out_distance;
+        // Solve every contact against the accumulated correction, so surfaces that share a
+        // normal, such as a floor made of several colliders, don't push us out twice.
+        let mut correction = Vec3::ZERO;
+        for &(normal, penetration) in &contacts {
+            let remaining = penetration + EPSILON - correction.dot(normal);
+            if remaining > 0.0 {
+                correction += normal * remaining;
+            }
+        }
+        transform.translation += correction;
     }
+
+    // Only report what the last correction didn't resolve.
+    penetrating_contacts(spatial_query, filter, colliders, collider, transform)
+        .into_iter()
+        .map(|(_, penetration)| penetration)
+        .reduce(f32::max)
+}
+
+/// Gathers the contacts of every collider overlapping the character.
+///
+/// Returns the direction to push the character out of each contact, and how deep it is.
+fn penetrating_contacts(
+    spatial_query: &mut spatial_query::SpatialQuery,
+    filter: &spatial_query::SpatialQueryFilter,
+    colliders: &Query<(&Collider, &Position, &Rotation)>,
+    collider: &Collider,
+    transform: &Transform,
+) -> Vec<(Vec3, f32)> {
+    spatial_query
+        .shape_intersections(collider, transform.translation, transform.rotation, filter)
+        .into_iter()
+        .filter_map(|other| {
+            let (other_collider, position, rotation) = colliders.get(other).ok()?;
+            let contact = contact_query::contact(
+                collider,
+                transform.translation,
+                transform.rotation,
+                other_collider,
+                *position,
+                *rotation,
+                0.0,
+            )
+            .ok()??;
+            // Contact normals are local to each collider, so bring ours into world space.
+            // It points out of the character, so we push the other way.
+            let normal = -(transform.rotation * contact.normal1);
+            (contact.penetration > 0.0).then_some((normal, contact.penetration))
+        })
+        .collect()
 }
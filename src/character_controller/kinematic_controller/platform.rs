@@ -0,0 +1,81 @@
+//! Moving platform support for the kinematic character controller.
+//!
+//! Characters standing on a kinematic or dynamic body are carried along by that body's linear and
+//! angular velocity, and turn along with it when it spins around their up vector. When they leave
+//! the floor, by jumping or walking off the edge, the floor's velocity is added to their own so
+//! that they keep its momentum.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use super::{
+    collider_body,
+    KCCFloorDetection,
+    KinematicCharacterController,
+};
+
+/// Moves characters along with the floor they are standing on.
+///
+/// This runs before `collide_and_slide_system`, using the floor found by `update_kinematic_floor`
+/// last tick.
+pub fn move_with_floor(
+    mut query: Query<(
+        Entity,
+        &mut Transform,
+        &mut KinematicCharacterController,
+        &mut KCCFloorDetection,
+    )>,
+    collider_parents: Query<&ColliderParent>,
+    bodies: Query<(&GlobalTransform, Option<&LinearVelocity>, Option<&AngularVelocity>)>,
+    spatial_query: SpatialQuery,
+    time: Res<Time>,
+) {
+    const EPSILON: f32 = 0.01;
+
+    for (entity, mut transform, mut controller, mut floor_detection) in &mut query {
+        let Some(floor) = floor_detection.floor_entity else {
+            // We left the floor, keep its momentum.
+            if floor_detection.floor_velocity != Vec3::ZERO {
+                controller.velocity += floor_detection.floor_velocity;
+                floor_detection.floor_velocity = Vec3::ZERO;
+            }
+            continue;
+        };
+
+        let body = collider_body(floor, &collider_parents);
+        let Ok((body_transform, linear_velocity, angular_velocity)) = bodies.get(body) else {
+            floor_detection.floor_velocity = Vec3::ZERO;
+            continue;
+        };
+
+        let linear_velocity = linear_velocity.map_or(Vec3::ZERO, |velocity| velocity.0);
+        let angular_velocity = angular_velocity.map_or(Vec3::ZERO, |velocity| velocity.0);
+        let offset = transform.translation - body_transform.translation();
+        floor_detection.floor_velocity = linear_velocity + angular_velocity.cross(offset);
+
+        // Turn with the floor, so the character keeps facing the same way relative to it.
+        let yaw = angular_velocity.dot(controller.up) * time.delta_seconds();
+        if let Ok(up) = Dir3::new(controller.up) {
+            transform.rotate_axis(up, yaw);
+        }
+
+        // Follow the floor, but don't let it carry us into walls.
+        let motion = floor_detection.floor_velocity * time.delta_seconds();
+        let Ok((direction, length)) = Dir3::new_and_length(motion) else {
+            continue;
+        };
+        let filter = SpatialQueryFilter::default().with_excluded_entities([entity, floor]);
+        let distance = spatial_query
+            .cast_shape(
+                &controller.collider,
+                transform.translation,
+                transform.rotation,
+                direction,
+                length,
+                false,
+                &filter,
+            )
+            .map_or(length, |hit| (hit.time_of_impact - EPSILON).max(0.0));
+        transform.translation += *direction * distance;
+    }
+}
@@ -1,43 +1,63 @@
-use avian3d::{
-    math::{
-        Quaternion,
-        Vector,
-    },
-    prelude::{
-        Collider,
-        LockedAxes,
-        PhysicsSet,
-        RigidBody,
-        ShapeCaster,
-    },
+use avian3d::prelude::{
+    Collider,
+    LockedAxes,
+    PhysicsSet,
+    RigidBody,
 };
 use bevy::{
     color::palettes::css,
     prelude::*,
 };
+use bevy_transform_interpolation::TranslationInterpolation;
 use camera_rig::TrackedEntity;
 use input::PlayerActions;
 use leafwing_input_manager::InputManagerBundle;
-use movement::Gravity;
+use movement::{
+    AbilityMotion,
+    Climb,
+    Crouch,
+    Dash,
+    Gravity,
+    Jump,
+    Mantle,
+    MovementSettings,
+    Slide,
+    Sprint,
+    Stamina,
+    Swim,
+    WallRun,
+};
 
 use crate::GameState;
 mod camera_rig;
+mod fall_damage;
+mod footsteps;
 mod input;
 mod kinematic_controller;
 mod movement;
+mod surface;
+#[cfg(test)]
+mod tests;
 mod weapon;
 
 pub fn plugin(app: &mut App) {
     app.add_plugins((
         camera_rig::plugin,
+        fall_damage::plugin,
+        footsteps::plugin,
         movement::plugin,
         input::plugin,
         kinematic_controller::plugin,
+        surface::plugin,
         weapon::plugin,
     ));
     app.configure_sets(
         FixedUpdate,
-        CharacterControllerSet::Input.run_if(in_state(GameState::Playing)),
+        (
+            CharacterControllerSet::Input.run_if(in_state(GameState::Playing)),
+            CharacterControllerSet::Kinematics,
+        )
+            .chain(),
     );
     app.configure_sets(
         PostUpdate,
@@ -51,6 +71,7 @@ pub fn plugin(app: &mut App) {
 #[derive(SystemSet, Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub enum CharacterControllerSet {
     Input,
+    Kinematics,
     CameraSync,
 }
 
@@ -61,34 +82,70 @@ pub struct CharacterControllerBundle {
     pub player: Player,
     pub input: InputManagerBundle<PlayerActions>,
     pub gravity: Gravity,
+    pub movement_settings: MovementSettings,
+    pub jump: Jump,
+    pub sprint: Sprint,
+    pub slide: Slide,
+    pub stamina: Stamina,
+    pub wall_run: WallRun,
+    pub mantle: Mantle,
+    pub dash: Dash,
+    pub ability_motion: AbilityMotion,
+    pub climb: Climb,
+    pub swim: Swim,
+    pub floor_surface: surface::FloorSurface,
+    pub footsteps: footsteps::Footsteps,
+    pub fall_damage: fall_damage::FallDamage,
     pub rigid_body: RigidBody,
     pub collider: Collider,
-    pub ground_caster: ShapeCaster,
     pub kinematic_controller: kinematic_controller::KinematicCharacterController,
     pub kcc_grounded: kinematic_controller::KCCGrounded,
     pub kcc_floor_detection: kinematic_controller::KCCFloorDetection,
+    pub kcc_wall_detection: kinematic_controller::KCCWallDetection,
+    pub kcc_push: kinematic_controller::KCCPush,
+    pub crouch: Crouch,
+    pub interpolation: TranslationInterpolation,
 }
 
 impl Default for CharacterControllerBundle {
     fn default() -> Self {
+        // All of the character's shapes are derived from its crouch settings so they stay in sync.
+        let crouch = Crouch::default();
         Self {
-            tracked_entity: TrackedEntity(Vec3::new(0.0, 0.5, 0.0)),
+            tracked_entity: TrackedEntity(Vec3::Y * crouch.standing_eye_height),
             current_player: CurrentPlayer,
             player: Player,
             input: InputManagerBundle::with_map(input::input_map()),
             gravity: Gravity::default(),
+            movement_settings: MovementSettings::default(),
+            jump: Jump::default(),
+            sprint: Sprint::default(),
+            slide: Slide::default(),
+            stamina: Stamina::default(),
+            wall_run: WallRun::default(),
+            mantle: Mantle::default(),
+            dash: Dash::default(),
+            ability_motion: AbilityMotion::default(),
+            climb: Climb::default(),
+            swim: Swim::default(),
+            floor_surface: surface::FloorSurface::default(),
+            footsteps: footsteps::Footsteps::default(),
+            fall_damage: fall_damage::FallDamage::default(),
             rigid_body: RigidBody::Kinematic,
-            collider: Capsule3d::new(0.4, 0.8).into(),
-            ground_caster: ShapeCaster::new(
-                Capsule3d::new(0.4, 0.8),
-                Vector::ZERO,
-                Quaternion::default(),
-                Dir3::NEG_Y,
-            )
-            .with_max_time_of_impact(0.1),
-            kinematic_controller: kinematic_controller::KinematicCharacterController::default(),
+            collider: crouch.standing_collider(),
+            kinematic_controller: kinematic_controller::KinematicCharacterController {
+                collider: crouch.standing_collider(),
+                ..Default::default()
+            },
             kcc_grounded: kinematic_controller::KCCGrounded::default(),
-            kcc_floor_detection: kinematic_controller::KCCFloorDetection::default(),
+            kcc_floor_detection: kinematic_controller::KCCFloorDetection {
+                floor_collider: crouch.standing_collider(),
+                ..Default::default()
+            },
+            kcc_wall_detection: kinematic_controller::KCCWallDetection::default(),
+            kcc_push: kinematic_controller::KCCPush::default(),
+            crouch,
+            interpolation: TranslationInterpolation,
         }
     }
 }
@@ -113,7 +170,9 @@ pub fn spawn_test_character(
         PbrBundle {
             mesh: meshes.add(Capsule3d { radius: 0.4, half_length: 0.4 }),
             material: materials.add(Color::from(css::DARK_CYAN)),
-            transform: Transform::from_translation(Vec3::new(0.0, 20.0, 0.0)),
+            // Face the same way as the camera does on spawn.
+            transform: Transform::from_translation(Vec3::new(0.0, 20.0, 0.0))
+                .looking_to(Vec3::Z, Vec3::Y),
             ..Default::default()
         },
         LockedAxes::ROTATION_LOCKED,
@@ -0,0 +1,230 @@
+//! Movement abilities for the character controller.
+//!
+//! An ability is a component that wraps an [`Ability`], which ties an action to a cooldown, a
+//! number of charges and an [`AbilityEffect`]. Each ability is its own component type, so every
+//! character can have a different kit. Velocity effects drive the character through collide and
+//! slide like any other movement, and teleports are swept with the character's collider, so neither
+//! can carry a character through walls. [`Dash`] is the first ability built on this.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use super::{
+    super::{
+        input::PlayerActions,
+        kinematic_controller::KinematicCharacterController,
+        CharacterControllerSet,
+    },
+    gravity_system,
+    mantle::mantle_system,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        apply_ability_motion
+            .after(gravity_system)
+            .before(mantle_system)
+            .in_set(CharacterControllerSet::Input),
+    );
+    add_ability::<Dash>(app);
+}
+
+/// Adds the systems that let characters use the ability `A`.
+pub fn add_ability<A: MovementAbility>(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        ability_system::<A>
+            .after(gravity_system)
+            .before(apply_ability_motion)
+            .in_set(CharacterControllerSet::Input),
+    );
+}
+
+/// A component that can be used as a movement ability.
+pub trait MovementAbility: Component {
+    /// The ability's settings and charges.
+    fn ability(&mut self) -> &mut Ability;
+}
+
+/// What happens when an ability is used.
+#[derive(Reflect, Debug, Clone, Copy)]
+pub enum AbilityEffect {
+    /// Moves the character at `speed` for `duration` seconds without gravity. Afterwards the
+    /// character's speed is capped to `exit_speed`.
+    Velocity { speed: f32, duration: f32, exit_speed: f32 },
+    /// Moves the character up to `distance` in an instant, stopping at the first obstacle.
+    Teleport { distance: f32 },
+}
+
+/// The settings and charges of a movement ability.
+#[derive(Reflect, Debug, Clone)]
+pub struct Ability {
+    /// The action that uses this ability.
+    pub action: PlayerActions,
+    /// How long it takes to regain a single charge, in seconds.
+    pub cooldown: f32,
+    /// The most charges the ability can hold.
+    pub max_charges: u32,
+    /// How many charges are currently available.
+    pub charges: u32,
+    /// Seconds spent recharging towards the next charge.
+    pub recharge_progress: f32,
+    /// What happens when the ability is used.
+    pub effect: AbilityEffect,
+}
+
+impl Ability {
+    /// Creates an ability with all of its charges available.
+    pub fn new(
+        action: PlayerActions,
+        cooldown: f32,
+        max_charges: u32,
+        effect: AbilityEffect,
+    ) -> Self {
+        Self { action, cooldown, max_charges, charges: max_charges, recharge_progress: 0.0, effect }
+    }
+
+    /// Regains charges over time.
+    pub fn recharge(&mut self, delta_seconds: f32) {
+        if self.charges >= self.max_charges {
+            self.recharge_progress = 0.0;
+            return;
+        }
+        self.recharge_progress += delta_seconds;
+        if self.recharge_progress >= self.cooldown {
+            self.recharge_progress -= self.cooldown;
+            self.charges += 1;
+        }
+    }
+
+    /// Uses up a charge, returning `false` if there was none left.
+    pub fn try_consume(&mut self) -> bool {
+        if self.charges == 0 {
+            return false;
+        }
+        self.charges -= 1;
+        true
+    }
+}
+
+/// A short burst of speed in the direction the character is moving, or facing if it isn't.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Dash(pub Ability);
+
+impl Default for Dash {
+    fn default() -> Self {
+        Self(Ability::new(
+            PlayerActions::Dash,
+            1.5,
+            2,
+            AbilityEffect::Velocity { speed: 24.0, duration: 0.15, exit_speed: 10.0 },
+        ))
+    }
+}
+
+impl MovementAbility for Dash {
+    fn ability(&mut self) -> &mut Ability {
+        &mut self.0
+    }
+}
+
+/// Component that holds the motion of the velocity ability a character is currently using.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
+pub struct AbilityMotion {
+    /// The velocity the ability moves the character at.
+    pub velocity: Vec3,
+    /// Seconds left until the ability's motion ends.
+    pub remaining: f32,
+    /// The character's speed is capped to this when the motion ends.
+    pub exit_speed: f32,
+}
+
+impl AbilityMotion {
+    /// Is an ability currently moving the character?
+    pub fn active(&self) -> bool {
+        self.remaining > 0.0
+    }
+}
+
+/// Recharges the ability `A` and uses it when its action is pressed.
+pub fn ability_system<A: MovementAbility>(
+    mut query: Query<(
+        Entity,
+        &ActionState<PlayerActions>,
+        &mut A,
+        &mut AbilityMotion,
+        &KinematicCharacterController,
+        &mut Transform,
+    )>,
+    spatial_query: SpatialQuery,
+    time: Res<Time>,
+) {
+    for (entity, action_state, mut ability, mut motion, kcc, mut transform) in &mut query {
+        let ability = ability.ability();
+        ability.recharge(time.delta_seconds());
+        if !action_state.just_pressed(&ability.action) || motion.active() {
+            continue;
+        }
+
+        // Go where the player is steering, or straight ahead without input.
+        let up = kcc.up;
+        let movement = action_state.clamped_axis_pair(&PlayerActions::Movement).xy();
+        let steer = transform.rotation * Vec3::new(movement.x, 0.0, -movement.y);
+        let Some(direction) = steer
+            .reject_from(up)
+            .try_normalize()
+            .or_else(|| transform.forward().reject_from(up).try_normalize())
+        else {
+            continue;
+        };
+        if !ability.try_consume() {
+            continue;
+        }
+
+        match ability.effect {
+            AbilityEffect::Velocity { speed, duration, exit_speed } => {
+                *motion =
+                    AbilityMotion { velocity: direction * speed, remaining: duration, exit_speed };
+            }
+            AbilityEffect::Teleport { distance } => {
+                const EPSILON: f32 = 0.01;
+                let filter = SpatialQueryFilter::default().with_excluded_entities([entity]);
+                let travel = spatial_query
+                    .cast_shape(
+                        &kcc.collider,
+                        transform.translation,
+                        transform.rotation,
+                        Dir3::new_unchecked(direction),
+                        distance,
+                        false,
+                        &filter,
+                    )
+                    .map_or(distance, |hit| (hit.time_of_impact - EPSILON).max(0.0));
+                transform.translation += direction * travel;
+            }
+        }
+    }
+}
+
+/// Drives characters with an active velocity ability. This runs after gravity, so gravity is
+/// suspended for the ability's duration.
+pub fn apply_ability_motion(
+    mut query: Query<(&mut AbilityMotion, &mut KinematicCharacterController)>,
+    time: Res<Time>,
+) {
+    for (mut motion, mut kcc) in &mut query {
+        if !motion.active() {
+            continue;
+        }
+        motion.remaining -= time.delta_seconds();
+        if motion.active() {
+            kcc.velocity = motion.velocity;
+        } else {
+            kcc.velocity = motion.velocity.clamp_length_max(motion.exit_speed);
+        }
+    }
+}
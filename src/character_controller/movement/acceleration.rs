@@ -0,0 +1,90 @@
+//! A Quake-style acceleration model for the character controller.
+//!
+//! Rather than setting the character's velocity directly, input only ever accelerates the character
+//! towards a wished-for direction and speed, while friction slows it down on the ground. Air
+//! control uses the same function with a tiny speed cap, which is what makes air-strafing and
+//! bunny hopping possible.
+
+use bevy::prelude::*;
+
+/// Component that describes how a character accelerates and decelerates.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct MovementSettings {
+    /// How quickly the character reaches its wish speed on the ground.
+    pub ground_acceleration: f32,
+    /// How quickly the character reaches its wish speed in the air.
+    pub air_acceleration: f32,
+    /// How quickly the character slows down on the ground.
+    pub friction: f32,
+    /// Below this speed, friction acts as if the character were moving at this speed, so that it
+    /// comes to a full stop quickly instead of creeping along.
+    pub stop_speed: f32,
+    /// The top speed that input can accelerate the character to on the ground.
+    pub max_speed: f32,
+    /// In the air, input only accelerates the character up to this speed along the wish direction,
+    /// while the acceleration itself still scales with the full wish speed. Keeping it low means
+    /// air control can mostly only turn the character's velocity, which is what enables
+    /// air-strafing.
+    pub max_air_wish_speed: f32,
+}
+
+impl Default for MovementSettings {
+    fn default() -> Self {
+        Self {
+            ground_acceleration: 10.0,
+            air_acceleration: 10.0,
+            friction: 6.0,
+            stop_speed: 2.5,
+            max_speed: 8.0,
+            max_air_wish_speed: 0.76,
+        }
+    }
+}
+
+/// Accelerates `velocity` towards `wish_speed` along `wish_direction`.
+///
+/// How quickly the velocity changes scales with the full `wish_speed`, but the velocity along
+/// `wish_direction` is only ever accelerated up to `wish_speed` capped to `speed_cap`. Only the
+/// part of the velocity along `wish_direction` is limited, so turning while moving faster than the
+/// cap still adds speed.
+pub fn accelerate(
+    velocity: Vec3,
+    wish_direction: Vec3,
+    wish_speed: f32,
+    speed_cap: f32,
+    acceleration: f32,
+    delta_seconds: f32,
+) -> Vec3 {
+    let current_speed = velocity.dot(wish_direction);
+    let add_speed = wish_speed.min(speed_cap) - current_speed;
+    if add_speed <= 0.0 {
+        return velocity;
+    }
+
+    let acceleration_speed = (acceleration * delta_seconds * wish_speed).min(add_speed);
+    velocity + wish_direction * acceleration_speed
+}
+
+/// Applies ground friction to the part of `velocity` that is perpendicular to `up`.
+///
+/// Below `stop_speed`, friction acts as if the character were moving at `stop_speed`.
+pub fn apply_friction(
+    velocity: Vec3,
+    up: Vec3,
+    friction: f32,
+    stop_speed: f32,
+    delta_seconds: f32,
+) -> Vec3 {
+    let vertical = velocity.project_onto(up);
+    let horizontal = velocity - vertical;
+    let speed = horizontal.length();
+    if speed < 0.001 {
+        return vertical;
+    }
+
+    let control = speed.max(stop_speed);
+    let drop = control * friction * delta_seconds;
+    let new_speed = (speed - drop).max(0.0);
+    vertical + horizontal * (new_speed / speed)
+}
@@ -0,0 +1,157 @@
+//! Crouching for the character controller.
+//!
+//! Crouching shrinks every collider the character uses and lowers the camera. Standing back up is
+//! refused while the taller capsule would overlap the ceiling, so players can crawl through vents.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use super::{
+    super::{
+        camera_rig::TrackedEntity,
+        input::PlayerActions,
+        kinematic_controller::{
+            KCCFloorDetection,
+            KCCGrounded,
+            KinematicCharacterController,
+        },
+        CharacterControllerSet,
+    },
+    align_to_gravity,
+    movement_input,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        crouch_system
+            .after(align_to_gravity)
+            .before(movement_input)
+            .in_set(CharacterControllerSet::Input),
+    );
+}
+
+/// Component that describes the standing and crouching shapes of a character.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Crouch {
+    /// Is this character currently crouching?
+    pub crouching: bool,
+    /// The radius of the character's capsule.
+    pub radius: f32,
+    /// The length of the capsule's cylindrical segment while standing.
+    pub standing_length: f32,
+    /// The length of the capsule's cylindrical segment while crouching.
+    pub crouching_length: f32,
+    /// The height of the camera above the capsule's center while standing.
+    pub standing_eye_height: f32,
+    /// The height of the camera above the capsule's center while crouching.
+    pub crouching_eye_height: f32,
+}
+
+impl Default for Crouch {
+    fn default() -> Self {
+        Self {
+            crouching: false,
+            radius: 0.4,
+            standing_length: 0.8,
+            crouching_length: 0.2,
+            standing_eye_height: 0.5,
+            crouching_eye_height: 0.2,
+        }
+    }
+}
+
+impl Crouch {
+    /// The collider used while standing.
+    pub fn standing_collider(&self) -> Collider {
+        Collider::capsule(self.radius, self.standing_length)
+    }
+
+    /// The collider used while crouching.
+    pub fn crouched_collider(&self) -> Collider {
+        Collider::capsule(self.radius, self.crouching_length)
+    }
+
+    /// How much shorter the character is while crouching.
+    pub fn height_difference(&self) -> f32 {
+        self.standing_length - self.crouching_length
+    }
+}
+
+/// Crouches while [`PlayerActions::Crouch`] is held and stands back up once it is released and
+/// there is room to do so.
+///
+/// On the ground the character's feet stay in place, so crouching lowers its head. In the air its
+/// head stays in place instead, so crouching tucks its legs up.
+pub fn crouch_system(
+    mut query: Query<(
+        Entity,
+        &ActionState<PlayerActions>,
+        &mut Crouch,
+        &mut Transform,
+        &mut Collider,
+        &mut KinematicCharacterController,
+        &mut KCCFloorDetection,
+        &KCCGrounded,
+        &mut TrackedEntity,
+    )>,
+    spatial_query: SpatialQuery,
+) {
+    for (
+        entity,
+        action_state,
+        mut crouch,
+        mut transform,
+        mut collider,
+        mut kcc,
+        mut floor_detection,
+        grounded,
+        mut tracked_entity,
+    ) in &mut query
+    {
+        let wants_to_crouch = action_state.pressed(&PlayerActions::Crouch);
+        if wants_to_crouch == crouch.crouching {
+            continue;
+        }
+
+        // The direction the capsule grows in when standing up.
+        let up = Dir3::new(kcc.up).unwrap_or(Dir3::Y);
+        let grow_direction = if grounded.grounded { up } else { -up };
+        let height_difference = crouch.height_difference();
+
+        if wants_to_crouch {
+            transform.translation -= *grow_direction * height_difference * 0.5;
+        } else {
+            // Check that the standing capsule would fit before we stand up.
+            let filter = SpatialQueryFilter::default().with_excluded_entities([entity]);
+            let obstructed = spatial_query
+                .cast_shape(
+                    &kcc.collider,
+                    transform.translation,
+                    transform.rotation,
+                    grow_direction,
+                    height_difference,
+                    false,
+                    &filter,
+                )
+                .is_some();
+            if obstructed {
+                continue;
+            }
+            transform.translation += *grow_direction * height_difference * 0.5;
+        }
+
+        crouch.crouching = wants_to_crouch;
+        let (shape, eye_height) = if crouch.crouching {
+            (crouch.crouched_collider(), crouch.crouching_eye_height)
+        } else {
+            (crouch.standing_collider(), crouch.standing_eye_height)
+        };
+        *collider = shape.clone();
+        kcc.collider = shape.clone();
+        floor_detection.floor_collider = shape;
+        tracked_entity.0 = Vec3::Y * eye_height;
+    }
+}
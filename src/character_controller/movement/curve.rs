@@ -0,0 +1,38 @@
+//! A small piecewise-linear curve for tuning movement from the inspector or a scene file.
+
+use bevy::prelude::*;
+
+/// A curve made of points joined by straight lines.
+///
+/// Sampling before the first point or after the last one returns that point's value, so curves
+/// don't need to cover every possible input.
+#[derive(Reflect, Debug, Clone, Default)]
+pub struct ResponseCurve {
+    /// The points of the curve, `x` is the input and `y` the output. They must be sorted by `x`.
+    pub points: Vec<Vec2>,
+}
+
+impl ResponseCurve {
+    /// Creates a curve from points sorted by `x`.
+    pub fn new(points: impl Into<Vec<Vec2>>) -> Self {
+        Self { points: points.into() }
+    }
+
+    /// Samples the curve at `x`. An empty curve is zero everywhere.
+    pub fn sample(&self, x: f32) -> f32 {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return 0.0;
+        };
+        if x <= first.x {
+            return first.y;
+        }
+        if x >= last.x {
+            return last.y;
+        }
+
+        let next = self.points.partition_point(|point| point.x <= x);
+        let (a, b) = (self.points[next - 1], self.points[next]);
+        let t = (x - a.x) / (b.x - a.x);
+        a.y.lerp(b.y, t)
+    }
+}
@@ -0,0 +1,129 @@
+//! Jumping for the character controller.
+//!
+//! Jumps are forgiving: a press shortly before landing is buffered and fires on touchdown, and a
+//! character that just walked off a ledge can still jump for a short coyote time. Releasing the
+//! button while still rising cuts the jump short, so tapping gives a hop and holding a full jump.
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use super::{
+    super::{
+        input::PlayerActions,
+        kinematic_controller::{
+            KCCGrounded,
+            KinematicCharacterController,
+        },
+        CharacterControllerSet,
+    },
+    align_to_gravity,
+    movement_input,
+    Gravity,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        jump_system
+            .after(align_to_gravity)
+            .before(movement_input)
+            .in_set(CharacterControllerSet::Input),
+    );
+}
+
+/// Component that describes how a character jumps, and tracks its jump state.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Jump {
+    /// The height of a full jump in meters.
+    pub height: f32,
+    /// How long after leaving the ground, in seconds, the character may still jump.
+    pub coyote_time: f32,
+    /// How long before landing, in seconds, a jump press is remembered.
+    pub buffer_time: f32,
+    /// The fraction of upward velocity kept when the button is released during a jump.
+    pub release_velocity_multiplier: f32,
+    /// Seconds since the character was last grounded.
+    pub time_since_grounded: f32,
+    /// Seconds since jump was last pressed.
+    pub time_since_pressed: f32,
+    /// Is the character rising from a jump? Movement treats it as airborne while it is, even if
+    /// the floor is still within reach.
+    pub jumping: bool,
+}
+
+impl Default for Jump {
+    fn default() -> Self {
+        Self {
+            height: 0.64,
+            coyote_time: 0.1,
+            buffer_time: 0.1,
+            release_velocity_multiplier: 0.5,
+            time_since_grounded: f32::INFINITY,
+            time_since_pressed: f32::INFINITY,
+            jumping: false,
+        }
+    }
+}
+
+impl Jump {
+    /// The initial upward speed needed to reach [`Jump::height`] under `gravity`.
+    pub fn velocity(&self, gravity: f32) -> f32 {
+        (2.0 * gravity * self.height).sqrt()
+    }
+}
+
+/// Starts jumps when a buffered press meets a grounded or coyote-time character, and cuts them
+/// short when the button is released early.
+pub fn jump_system(
+    mut query: Query<(
+        &ActionState<PlayerActions>,
+        &mut Jump,
+        &mut KinematicCharacterController,
+        &KCCGrounded,
+        &Gravity,
+    )>,
+    time: Res<Time>,
+) {
+    for (action_state, mut jump, mut kcc, grounded, gravity) in &mut query {
+        let delta_seconds = time.delta_seconds();
+        let up = kcc.up;
+        let vertical_speed = kcc.velocity.dot(up);
+
+        // A jump is over once we stop rising.
+        if jump.jumping && vertical_speed <= 0.0 {
+            jump.jumping = false;
+        }
+
+        // Variable jump height, releasing early cuts the remaining rise.
+        if jump.jumping && !action_state.pressed(&PlayerActions::Jump) {
+            kcc.velocity -= up * vertical_speed * (1.0 - jump.release_velocity_multiplier);
+            jump.jumping = false;
+        }
+
+        // The floor cast can still reach the ground for a tick after jumping, so don't let that
+        // refresh coyote time.
+        if grounded.grounded && !jump.jumping {
+            jump.time_since_grounded = 0.0;
+        } else {
+            jump.time_since_grounded += delta_seconds;
+        }
+
+        if action_state.just_pressed(&PlayerActions::Jump) {
+            jump.time_since_pressed = 0.0;
+        } else {
+            jump.time_since_pressed += delta_seconds;
+        }
+
+        let buffered = jump.time_since_pressed <= jump.buffer_time;
+        let can_jump = jump.time_since_grounded <= jump.coyote_time;
+        if buffered && can_jump {
+            let jump_velocity = jump.velocity(gravity.0.length());
+            kcc.velocity = kcc.velocity.reject_from(up) + up * jump_velocity;
+            // Consume both the press and the ground, so a single press can't jump twice.
+            jump.time_since_pressed = f32::INFINITY;
+            jump.time_since_grounded = f32::INFINITY;
+            jump.jumping = true;
+        }
+    }
+}
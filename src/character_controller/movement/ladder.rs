@@ -0,0 +1,157 @@
+//! Ladders and other climbable volumes for the character controller.
+//!
+//! A ladder is a collider tagged with [`Ladder`], usually from Blender through Blenvy. Ladders are
+//! turned into sensors that spatial queries ignore, so they never block the character. While a
+//! character is inside one, gravity is suspended and forward input climbs along the ladder's local
+//! `Y` axis, up when looking up and down when looking down. The ladder's local `Z` axis should
+//! point away from the wall, towards the climber.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use super::{
+    super::{
+        camera_rig::RiggedCamera,
+        input::PlayerActions,
+        kinematic_controller::{
+            KCCGrounded,
+            KinematicCharacterController,
+        },
+        CharacterControllerSet,
+    },
+    ability::apply_ability_motion,
+    gravity_system,
+    prepare_volumes,
+    Jump,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Ladder>();
+    app.add_systems(
+        FixedUpdate,
+        (
+            prepare_volumes::<Ladder>.before(CharacterControllerSet::Input),
+            climb_system
+                .after(gravity_system)
+                .before(apply_ability_motion)
+                .in_set(CharacterControllerSet::Input),
+        ),
+    );
+}
+
+/// Marker component for climbable volumes. It is registered for reflection, so it can be added to
+/// objects in Blender through Blenvy.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
+pub struct Ladder;
+
+/// Component that describes how a character climbs ladders, and tracks its climbing state.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Climb {
+    /// How fast the character climbs along a ladder.
+    pub speed: f32,
+    /// How fast the character can move sideways on a ladder.
+    pub strafe_speed: f32,
+    /// Looking further down than this pitch, in radians, makes forward climb down.
+    pub down_pitch: f32,
+    /// The speed the character is pushed away from a ladder with when it jumps off.
+    pub jump_off_speed: f32,
+    /// The upward speed the character gets when it jumps off a ladder.
+    pub jump_off_up_speed: f32,
+    /// The speed the character is pushed onto the landing when it climbs off the top of a ladder.
+    pub top_dismount_speed: f32,
+    /// How long after jumping off a ladder, in seconds, before the character can grab one again.
+    pub regrab_delay: f32,
+    /// Is the character currently climbing?
+    pub climbing: bool,
+    /// The direction the ladder being climbed faces, away from its wall.
+    pub ladder_facing: Vec3,
+    /// Seconds since the character last jumped off a ladder.
+    pub time_since_jump_off: f32,
+}
+
+impl Default for Climb {
+    fn default() -> Self {
+        Self {
+            speed: 3.0,
+            strafe_speed: 1.5,
+            down_pitch: -0.3,
+            jump_off_speed: 4.0,
+            jump_off_up_speed: 3.0,
+            top_dismount_speed: 3.0,
+            regrab_delay: 0.3,
+            climbing: false,
+            ladder_facing: Vec3::Z,
+            time_since_jump_off: f32::INFINITY,
+        }
+    }
+}
+
+/// Mounts, moves and dismounts characters on ladders.
+///
+/// A grounded character only mounts a ladder by climbing up it, so it can walk past ladders and
+/// step off the bottom of one by climbing down onto the floor. Climbing out of the top of a ladder
+/// pushes the character onto the landing, and jumping pushes it away from the ladder.
+pub fn climb_system(
+    mut query: Query<(
+        &ActionState<PlayerActions>,
+        &mut Climb,
+        &mut KinematicCharacterController,
+        &KCCGrounded,
+        &Transform,
+        Option<&mut Jump>,
+    )>,
+    ladders: Query<(&Collider, &Position, &Rotation), With<Ladder>>,
+    camera: Query<&Transform, With<RiggedCamera>>,
+    time: Res<Time>,
+) {
+    let camera_forward = camera.get_single().map_or(Vec3::ZERO, |camera| *camera.forward());
+    for (action_state, mut climb, mut kcc, grounded, transform, mut jump) in &mut query {
+        climb.time_since_jump_off += time.delta_seconds();
+        let up = kcc.up;
+        let look_pitch = camera_forward.dot(up).clamp(-1.0, 1.0).asin();
+        let ladder = ladders
+            .iter()
+            .find(|(collider, position, rotation)| {
+                collider.contains_point(**position, **rotation, transform.translation)
+            })
+            .map(|(_, _, rotation)| rotation);
+
+        let Some(rotation) = ladder.filter(|_| climb.time_since_jump_off >= climb.regrab_delay)
+        else {
+            // Climbing up out of the ladder, give a push onto the landing.
+            if climb.climbing && kcc.velocity.dot(up) > 0.0 {
+                kcc.velocity -= climb.ladder_facing * climb.top_dismount_speed;
+            }
+            climb.climbing = false;
+            continue;
+        };
+
+        let movement = action_state.clamped_axis_pair(&PlayerActions::Movement).xy();
+        let direction = if look_pitch < climb.down_pitch { -1.0 } else { 1.0 };
+        let climb_input = movement.y * direction;
+        if grounded.grounded && climb_input <= 0.0 {
+            climb.climbing = false;
+            continue;
+        }
+
+        let axis = **rotation * Vec3::Y;
+        let facing = (**rotation * Vec3::Z).reject_from(up).normalize_or_zero();
+        climb.climbing = true;
+        climb.ladder_facing = facing;
+
+        if let Some(jump) = jump.as_mut().filter(|jump| jump.time_since_pressed <= jump.buffer_time)
+        {
+            kcc.velocity = facing * climb.jump_off_speed + up * climb.jump_off_up_speed;
+            jump.time_since_pressed = f32::INFINITY;
+            climb.climbing = false;
+            climb.time_since_jump_off = 0.0;
+            continue;
+        }
+
+        let right = axis.cross(facing).normalize_or_zero();
+        kcc.velocity = axis * climb_input * climb.speed + right * movement.x * climb.strafe_speed;
+    }
+}
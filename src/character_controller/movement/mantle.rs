@@ -0,0 +1,208 @@
+//! Ledge grabbing and mantling for the character controller.
+//!
+//! An airborne character that reaches a ledge slightly above it pulls itself up onto the ledge
+//! instead of sliding down the wall. Ledges are found with shape casts of the character's own
+//! collider, forwards to find the wall and then down from above to find its top. The mantle itself
+//! drives the character's velocity, so it still goes through collide and slide.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use super::{
+    super::{
+        input::PlayerActions,
+        kinematic_controller::{
+            KCCFloorDetection,
+            KCCGrounded,
+            KinematicCharacterController,
+        },
+        CharacterControllerSet,
+    },
+    gravity_system,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        mantle_system.after(gravity_system).in_set(CharacterControllerSet::Input),
+    );
+}
+
+/// Component that describes how a character mantles onto ledges, and tracks its mantle state.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Mantle {
+    /// The highest ledge, measured from the character's feet, that it can mantle onto.
+    pub max_height: f32,
+    /// How far in front of the character a wall can be for its ledge to be grabbed.
+    pub reach: f32,
+    /// How far past the face of the wall the character ends up on the ledge.
+    pub ledge_depth: f32,
+    /// How long a mantle takes, in seconds.
+    pub duration: f32,
+    /// Does the character have to hold forward to grab a ledge?
+    pub require_input: bool,
+    /// Is the character currently mantling?
+    pub mantling: bool,
+    /// How far along the current mantle is, from `0.0` to `1.0`.
+    pub progress: f32,
+    /// Where the current mantle started.
+    pub start: Vec3,
+    /// The corner of the mantle's path, right above `start` and level with `target`.
+    pub corner: Vec3,
+    /// Where the current mantle ends, on top of the ledge.
+    pub target: Vec3,
+}
+
+impl Default for Mantle {
+    fn default() -> Self {
+        Self {
+            max_height: 1.2,
+            reach: 0.4,
+            ledge_depth: 0.5,
+            duration: 0.4,
+            require_input: true,
+            mantling: false,
+            progress: 0.0,
+            start: Vec3::ZERO,
+            corner: Vec3::ZERO,
+            target: Vec3::ZERO,
+        }
+    }
+}
+
+impl Mantle {
+    /// The point `progress` of the way along the mantle's path, which first rises and then moves
+    /// onto the ledge.
+    pub fn point_at(&self, progress: f32) -> Vec3 {
+        let rise = self.start.distance(self.corner);
+        let forward = self.corner.distance(self.target);
+        let distance = (rise + forward) * progress.clamp(0.0, 1.0);
+        if distance <= rise || forward == 0.0 {
+            self.start.lerp(self.corner, (distance / rise.max(f32::EPSILON)).min(1.0))
+        } else {
+            self.corner.lerp(self.target, (distance - rise) / forward)
+        }
+    }
+}
+
+/// Grabs ledges and moves mantling characters along their path.
+///
+/// This runs after gravity, so a mantling character's velocity is fully decided here.
+pub fn mantle_system(
+    mut query: Query<(
+        Entity,
+        &ActionState<PlayerActions>,
+        &mut Mantle,
+        &mut KinematicCharacterController,
+        &KCCGrounded,
+        &KCCFloorDetection,
+        &Transform,
+    )>,
+    spatial_query: SpatialQuery,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_seconds();
+    for (entity, action_state, mut mantle, mut kcc, grounded, floor_detection, transform) in
+        &mut query
+    {
+        if !mantle.mantling {
+            let wants_to_mantle = !mantle.require_input
+                || action_state.clamped_axis_pair(&PlayerActions::Movement).y > 0.0;
+            if grounded.grounded || !wants_to_mantle {
+                continue;
+            }
+            let filter = SpatialQueryFilter::default().with_excluded_entities([entity]);
+            let Some((corner, target)) =
+                find_ledge(&spatial_query, &filter, &kcc, floor_detection, transform, &mantle)
+            else {
+                continue;
+            };
+            mantle.mantling = true;
+            mantle.progress = 0.0;
+            mantle.start = transform.translation;
+            mantle.corner = corner;
+            mantle.target = target;
+        }
+
+        mantle.progress = (mantle.progress + delta_seconds / mantle.duration).min(1.0);
+        let next = mantle.point_at(mantle.progress);
+        kcc.velocity = (next - transform.translation) / delta_seconds;
+        if mantle.progress >= 1.0 {
+            mantle.mantling = false;
+        }
+    }
+}
+
+/// Looks for a ledge in front of the character that it can mantle onto.
+///
+/// Returns the corner and the end of the mantle's path if there is one.
+fn find_ledge(
+    spatial_query: &SpatialQuery,
+    filter: &SpatialQueryFilter,
+    kcc: &KinematicCharacterController,
+    floor_detection: &KCCFloorDetection,
+    transform: &Transform,
+    mantle: &Mantle,
+) -> Option<(Vec3, Vec3)> {
+    const EPSILON: f32 = 0.01;
+    let up = Dir3::new(kcc.up).ok()?;
+    let forward = Dir3::new(transform.forward().reject_from(*up)).ok()?;
+    let collider = &kcc.collider;
+    let rotation = transform.rotation;
+
+    // Find the wall in front of us.
+    let wall = spatial_query.cast_shape(
+        collider,
+        transform.translation,
+        rotation,
+        forward,
+        mantle.reach,
+        false,
+        filter,
+    )?;
+    if wall.normal1.dot(*up).abs() > 0.3 {
+        return None;
+    }
+
+    // Look down onto the top of the wall from our highest reach. Starting inside geometry means the
+    // wall is too tall.
+    let probe = transform.translation
+        + *up * mantle.max_height
+        + *forward * (wall.time_of_impact + mantle.ledge_depth);
+    let ledge = spatial_query.cast_shape(
+        collider,
+        probe,
+        rotation,
+        -up,
+        mantle.max_height,
+        false,
+        filter,
+    )?;
+    if ledge.time_of_impact <= 0.0 || !floor_detection.is_walkable(ledge.normal1, *up) {
+        return None;
+    }
+    let target = probe - *up * (ledge.time_of_impact - EPSILON).max(0.0);
+    let rise = (target - transform.translation).dot(*up);
+    if rise <= 0.0 {
+        return None;
+    }
+
+    // Make sure there is room to climb up and then over.
+    let corner = transform.translation + *up * rise;
+    let blocked_above = spatial_query
+        .cast_shape(collider, transform.translation, rotation, up, rise, false, filter)
+        .is_some();
+    let over = target - corner;
+    let blocked_over = Dir3::new(over).is_ok_and(|direction| {
+        spatial_query
+            .cast_shape(collider, corner, rotation, direction, over.length(), false, filter)
+            .is_some()
+    });
+    if blocked_above || blocked_over {
+        return None;
+    }
+
+    Some((corner, target))
+}
@@ -1,10 +1,6 @@
-use avian3d::{
-    math::Scalar,
-    prelude::{
-        RigidBody,
-        Rotation,
-        ShapeHits,
-    },
+use avian3d::prelude::{
+    CollisionLayers,
+    Sensor,
 };
 use bevy::prelude::*;
 use leafwing_input_manager::{
@@ -15,29 +11,89 @@ use leafwing_input_manager::{
 use super::{
     camera_rig::RiggedCamera,
     input::PlayerActions,
-    kinematic_controller::KinematicCharacterController,
+    kinematic_controller::{
+        KCCFloorDetection,
+        KCCGrounded,
+        KinematicCharacterController,
+    },
+    surface::{
+        FloorSurface,
+        SurfaceMaterial,
+    },
     CharacterControllerSet,
 };
+use crate::GameState;
+
+mod ability;
+mod acceleration;
+mod crouch;
+mod curve;
+mod jump;
+mod ladder;
+mod mantle;
+mod slide;
+mod sprint;
+mod swim;
+mod wall_run;
+pub use ability::{
+    add_ability,
+    Ability,
+    AbilityEffect,
+    AbilityMotion,
+    Dash,
+    MovementAbility,
+};
+pub use acceleration::MovementSettings;
+pub use crouch::Crouch;
+pub use curve::ResponseCurve;
+pub use jump::Jump;
+pub use ladder::{
+    Climb,
+    Ladder,
+};
+pub use mantle::Mantle;
+pub use slide::Slide;
+pub use sprint::{
+    Sprint,
+    Stamina,
+};
+pub use swim::{
+    character_feet,
+    Swim,
+    WaterVolume,
+};
+pub use wall_run::{
+    WallRun,
+    WallRunnable,
+};
 
 pub fn plugin(app: &mut App) {
+    app.add_plugins((
+        ability::plugin,
+        crouch::plugin,
+        jump::plugin,
+        ladder::plugin,
+        mantle::plugin,
+        slide::plugin,
+        sprint::plugin,
+        swim::plugin,
+        wall_run::plugin,
+    ));
     app.add_systems(
         FixedUpdate,
-        (velocity_dampening, update_grounded, movement_input, gravity_system)
+        (align_to_gravity, movement_input, gravity_system)
             .chain()
             .in_set(CharacterControllerSet::Input),
     );
+    app.add_systems(Update, camera_input.run_if(in_state(GameState::Playing)));
     app.add_plugins(InputManagerPlugin::<PlayerActions>::default());
 }
 
-// Marker component for whether or not we're currently grounded.
-#[derive(Component, Reflect, Debug, Default)]
-#[reflect(Component)]
-#[component(storage = "SparseSet")]
-pub struct Grounded;
-
+/// The acceleration due to gravity for a character. Its direction also defines which way is up for
+/// the character, so changing it lets characters walk on walls or around planetoids.
 #[derive(Component, Reflect, Debug)]
 #[reflect(Component)]
-pub struct Gravity(Vec3);
+pub struct Gravity(pub Vec3);
 
 impl Default for Gravity {
     fn default() -> Self {
@@ -45,15 +101,78 @@ impl Default for Gravity {
     }
 }
 
-/// System that handles player movement and camera rotation based on input
+/// System that handles player movement based on input
 ///
-/// This system processes player actions and updates the character's movement and camera
-/// orientation. It handles horizontal movement, jumping, and camera rotation using mouse input.
+/// This system processes player actions and updates the character's velocity. It handles
+/// horizontal movement, and runs on the fixed timestep together with the rest of the character
+/// controller. Jumping and sliding are handled beforehand by their own systems, and swimming
+/// replaces it entirely.
 pub fn movement_input(
     mut player_query: Query<(
         &ActionState<PlayerActions>,
         &mut KinematicCharacterController,
-        Has<Grounded>,
+        &MovementSettings,
+        &KCCGrounded,
+        Option<&Jump>,
+        Option<&Sprint>,
+        Option<&Slide>,
+        Option<&Swim>,
+        Option<&FloorSurface>,
+        &Transform,
+    )>,
+    time: Res<Time>,
+) {
+    // Early return if we can't get the player
+    let Ok((
+        action_state,
+        mut kcc,
+        settings,
+        grounded,
+        jump,
+        sprint,
+        slide,
+        swim,
+        floor_surface,
+        player_transform,
+    )) = player_query.get_single_mut()
+    else {
+        return;
+    };
+    // Swimming characters are moved by the swim system instead.
+    if swim.is_some_and(|swim| swim.swimming) {
+        return;
+    }
+
+    // A character rising from a jump is airborne, even if the floor is still in reach. A sliding
+    // character gets its friction from the slide, so it only gets air control here.
+    let jumping = jump.is_some_and(|jump| jump.jumping);
+    let sliding = slide.is_some_and(|slide| slide.sliding);
+    let speed_multiplier =
+        sprint.filter(|sprint| sprint.sprinting).map_or(1.0, |sprint| sprint.speed_multiplier);
+    let surface =
+        floor_surface.map_or_else(SurfaceMaterial::default, |floor_surface| floor_surface.0);
+    update_player_movement(
+        action_state,
+        &mut kcc,
+        settings,
+        speed_multiplier,
+        surface,
+        grounded.grounded && !jumping && !sliding,
+        player_transform,
+        time.delta_seconds(),
+    );
+}
+
+/// System that handles camera rotation based on mouse input
+///
+/// This runs every frame rather than on the fixed timestep, so looking around stays responsive at
+/// any frame rate. Only the player's rotation is written here, its translation is left to the
+/// fixed timestep and interpolated. The camera also eases towards the roll of a wall run here.
+pub fn camera_input(
+    mut player_query: Query<(
+        &ActionState<PlayerActions>,
+        &KinematicCharacterController,
+        Option<&WallRun>,
         &mut Transform,
     )>,
     mut camera_query: Query<
@@ -63,95 +182,157 @@ pub fn movement_input(
     time: Res<Time>,
 ) {
     // Early return if we can't get the player or camera
-    let Ok((action_state, mut kcc, grounded, mut player_transform)) = player_query.get_single_mut()
+    let Ok((action_state, kcc, wall_run, mut player_transform)) = player_query.get_single_mut()
     else {
         return;
     };
     let Ok(mut camera_transform) = camera_query.get_single_mut() else { return };
 
-    update_player_movement(action_state, &mut kcc, grounded, &player_transform);
+    let (target_roll, roll_speed) = wall_run.map_or((0.0, 8.0), |wall_run| {
+        (wall_run.target_roll(*player_transform.right()), wall_run.camera_roll_speed)
+    });
+    let roll_blend = 1.0 - (-roll_speed * time.delta_seconds()).exp();
     update_camera_rotation(
         action_state,
         &mut camera_transform,
         &mut player_transform,
-        time.delta_seconds(),
+        kcc.up,
+        target_roll,
+        roll_blend,
     );
 }
 
 /// Updates the player's movement based on input
+///
+/// Grounded characters get friction and ground acceleration, while airborne ones only get air
+/// acceleration. A character that jumps on the tick it lands counts as airborne, so it skips
+/// friction and keeps its speed. `speed_multiplier` scales the max speed, for example while
+/// sprinting. The `surface` the character walks on further scales its ground movement.
 fn update_player_movement(
     action_state: &ActionState<PlayerActions>,
     kcc: &mut KinematicCharacterController,
+    settings: &MovementSettings,
+    speed_multiplier: f32,
+    surface: SurfaceMaterial,
     grounded: bool,
     player_transform: &Transform,
+    delta_seconds: f32,
 ) {
-    // Handle horizontal movement
+    // Handle horizontal movement. The player is kept upright relative to its up vector, so this is
+    // already perpendicular to it.
     let movement = action_state.clamped_axis_pair(&PlayerActions::Movement).xy();
-    let direction = player_transform
+    let wish_direction = player_transform
         .rotation
         .mul_vec3(Vec3::new(movement.x, 0.0, -movement.y))
-        .normalize_or_zero()
-        * 2.0;
-
-    if movement != Vec2::ZERO {
-        kcc.velocity.x = direction.x;
-        kcc.velocity.z = direction.z;
-    }
+        .normalize_or_zero();
+    let wish_speed = settings.max_speed * speed_multiplier * movement.length().min(1.0);
 
-    // Handle jumping
-    if action_state.pressed(&PlayerActions::Jump) && grounded {
-        kcc.velocity.y = 5.0;
+    if grounded {
+        kcc.velocity = acceleration::apply_friction(
+            kcc.velocity,
+            kcc.up,
+            settings.friction * surface.friction_multiplier(),
+            settings.stop_speed,
+            delta_seconds,
+        );
+        kcc.velocity = acceleration::accelerate(
+            kcc.velocity,
+            wish_direction,
+            wish_speed * surface.speed_multiplier(),
+            wish_speed * surface.speed_multiplier(),
+            settings.ground_acceleration * surface.acceleration_multiplier(),
+            delta_seconds,
+        );
+    } else {
+        kcc.velocity = acceleration::accelerate(
+            kcc.velocity,
+            wish_direction,
+            wish_speed,
+            settings.max_air_wish_speed,
+            settings.air_acceleration,
+            delta_seconds,
+        );
     }
 }
 
 /// Updates the camera and player rotation based on mouse input
+///
+/// Yaw turns the player around its up vector, while pitch only tilts the camera relative to the
+/// player. The player is first re-aligned with `up`, twisting as little as possible, so the view
+/// follows along when gravity changes direction. The camera's roll moves `roll_blend` of the way
+/// towards `target_roll`.
 fn update_camera_rotation(
     action_state: &ActionState<PlayerActions>,
     camera_transform: &mut Transform,
     player_transform: &mut Transform,
-    delta_time: f32,
+    up: Vec3,
+    target_roll: f32,
+    roll_blend: f32,
 ) {
-    let sensitivity = Vec2::new(0.12, 0.10);
-    let mouse_delta = action_state.axis_pair(&PlayerActions::Camera) * delta_time * sensitivity;
-    let (mut yaw, mut pitch, _) = camera_transform.rotation.to_euler(EulerRot::YXZ);
+    // Radians per pixel of mouse movement. Mouse deltas are already per frame, so they must not be
+    // scaled by the frame time.
+    let sensitivity = Vec2::new(0.0019, 0.0016);
+    let mouse_delta = action_state.axis_pair(&PlayerActions::Camera) * sensitivity;
+
+    let player_up = player_transform.rotation * Vec3::Y;
+    let alignment = Quat::from_rotation_arc(player_up, up.normalize());
+    player_transform.rotation =
+        (alignment * player_transform.rotation * Quat::from_rotation_y(-mouse_delta.x)).normalize();
+
+    let local_rotation = player_transform.rotation.inverse() * camera_transform.rotation;
+    let (_, mut pitch, roll) = local_rotation.to_euler(EulerRot::YXZ);
     pitch = (pitch + -mouse_delta.y).clamp(-1.54, 1.54);
-    yaw -= mouse_delta.x;
+    let roll = roll.lerp(target_roll, roll_blend);
 
-    camera_transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0);
-    player_transform.rotation = Quat::from_rotation_y(yaw);
+    camera_transform.rotation =
+        player_transform.rotation * Quat::from_rotation_x(pitch) * Quat::from_rotation_z(roll);
 }
 
-pub fn velocity_dampening(mut query: Query<&mut KinematicCharacterController>, _time: Res<Time>) {
-    for mut kcc in query.iter_mut() {
-        kcc.velocity.x *= 0.9;
-        kcc.velocity.z *= 0.9;
+/// Points each character's up vector and floor detection away from and towards its gravity.
+pub fn align_to_gravity(
+    mut query: Query<(&Gravity, &mut KinematicCharacterController, Option<&mut KCCFloorDetection>)>,
+) {
+    for (gravity, mut kcc, floor_detection) in query.iter_mut() {
+        let Some(down) = gravity.0.try_normalize() else {
+            continue;
+        };
+        kcc.up = -down;
+        if let Some(mut floor_detection) = floor_detection {
+            floor_detection.ground_direction = down;
+        }
     }
 }
 
-pub fn gravity_system(
-    mut query: Query<(&mut KinematicCharacterController, &Gravity, Has<Grounded>)>,
-    time: Res<Time>,
-) {
-    for (mut kcc, gravity, grounded) in query.iter_mut() {
-        kcc.velocity += gravity.0 * time.delta_seconds();
-        if grounded && gravity.0.dot(kcc.velocity) > -0.01 {
-            kcc.velocity.y = 0.0;
-        }
+/// Turns newly tagged volumes, such as ladders or water, into sensors that aren't part of any
+/// collision layer, so the character controller's shape casts pass right through them.
+pub fn prepare_volumes<T: Component>(mut commands: Commands, volumes: Query<Entity, Added<T>>) {
+    for volume in &volumes {
+        commands.entity(volume).insert((Sensor, CollisionLayers::NONE));
     }
 }
 
-fn update_grounded(
-    mut commands: Commands,
-    mut query: Query<(Entity, &ShapeHits, &Rotation), (With<Gravity>, With<RigidBody>)>,
+/// Accelerates characters along their gravity. Wall running characters only get a fraction of it,
+/// and swimming characters get buoyancy from the swim system instead.
+pub fn gravity_system(
+    mut query: Query<(
+        &mut KinematicCharacterController,
+        &Gravity,
+        &KCCGrounded,
+        Option<&WallRun>,
+        Option<&Swim>,
+    )>,
+    time: Res<Time>,
 ) {
-    let _ = (45.0 as Scalar).to_radians();
-    for (entity, hits, _) in &mut query {
-        let is_grounded = hits.iter().any(|hit| true && hit.entity != entity);
-
-        if is_grounded {
-            commands.entity(entity).insert(Grounded);
-        } else {
-            commands.entity(entity).remove::<Grounded>();
+    for (mut kcc, gravity, grounded, wall_run, swim) in query.iter_mut() {
+        if swim.is_some_and(|swim| swim.swimming) {
+            continue;
+        }
+        let scale = wall_run
+            .filter(|wall_run| wall_run.running)
+            .map_or(1.0, |wall_run| wall_run.gravity_multiplier);
+        kcc.velocity += gravity.0 * scale * time.delta_seconds();
+        if grounded.grounded && gravity.0.dot(kcc.velocity) > -0.01 {
+            kcc.velocity = kcc.velocity.reject_from(kcc.up);
         }
     }
 }
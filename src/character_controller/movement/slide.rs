@@ -0,0 +1,170 @@
+//! Sliding for the character controller.
+//!
+//! Crouching while moving at least at sprint speed starts a slide. A sliding character keeps its
+//! momentum with very little friction, picks up speed going downhill and loses it going uphill. The
+//! slide ends when the character slows down, stands up or leaves the ground, and jumping out of a
+//! slide keeps all of its speed. How the slide feels is tuned with the curves on [`Slide`].
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use super::{
+    super::{
+        input::PlayerActions,
+        kinematic_controller::{
+            KCCFloorDetection,
+            KCCGrounded,
+            KinematicCharacterController,
+        },
+        surface::FloorSurface,
+        CharacterControllerSet,
+    },
+    acceleration,
+    crouch::crouch_system,
+    jump::jump_system,
+    movement_input,
+    Crouch,
+    Gravity,
+    Jump,
+    MovementSettings,
+    ResponseCurve,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        slide_system
+            .after(crouch_system)
+            .after(jump_system)
+            .before(movement_input)
+            .in_set(CharacterControllerSet::Input),
+    );
+}
+
+/// Component that describes how a character slides, and tracks its slide state.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Slide {
+    /// The horizontal speed the character needs to start a slide.
+    pub min_start_speed: f32,
+    /// Speed added along the character's direction of travel when a slide starts.
+    pub start_boost: f32,
+    /// The slide ends once the horizontal speed drops below this.
+    pub min_speed: f32,
+    /// Sliding can never make the character faster than this.
+    pub max_speed: f32,
+    /// Maps seconds spent sliding to a multiplier of [`MovementSettings::friction`].
+    pub friction: ResponseCurve,
+    /// Maps the floor's slope angle, in radians, to the acceleration downhill in meters per second
+    /// squared. Sliding uphill decelerates by the same amount.
+    pub downhill_acceleration: ResponseCurve,
+    /// Is the character currently sliding?
+    pub sliding: bool,
+    /// Seconds since the current slide started.
+    pub time_sliding: f32,
+}
+
+impl Default for Slide {
+    fn default() -> Self {
+        Self {
+            min_start_speed: 9.0,
+            start_boost: 1.5,
+            min_speed: 4.0,
+            max_speed: 20.0,
+            friction: ResponseCurve::new([
+                Vec2::new(0.0, 0.05),
+                Vec2::new(0.6, 0.1),
+                Vec2::new(1.5, 1.0),
+            ]),
+            downhill_acceleration: ResponseCurve::new([
+                Vec2::new(0.0, 0.0),
+                Vec2::new(0.1, 2.0),
+                Vec2::new(0.8, 12.0),
+            ]),
+            sliding: false,
+            time_sliding: 0.0,
+        }
+    }
+}
+
+/// Starts, updates and ends slides.
+///
+/// A slide starts when a fast, grounded character crouches, or lands while already crouched.
+/// While sliding, this system applies the slide's friction and slope acceleration in place of the
+/// regular ground movement.
+pub fn slide_system(
+    mut query: Query<(
+        &ActionState<PlayerActions>,
+        &mut Slide,
+        &mut KinematicCharacterController,
+        &MovementSettings,
+        &KCCGrounded,
+        &KCCFloorDetection,
+        &Crouch,
+        &Gravity,
+        Option<&Jump>,
+        Option<&FloorSurface>,
+    )>,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_seconds();
+    for (
+        action_state,
+        mut slide,
+        mut kcc,
+        settings,
+        grounded,
+        floor_detection,
+        crouch,
+        gravity,
+        jump,
+        floor_surface,
+    ) in &mut query
+    {
+        let up = kcc.up;
+        let horizontal = kcc.velocity.reject_from(up);
+        let speed = horizontal.length();
+        let jumping = jump.is_some_and(|jump| jump.jumping);
+        let on_ground = grounded.grounded && !jumping;
+
+        if !slide.sliding {
+            let crouched_now = action_state.just_pressed(&PlayerActions::Crouch);
+            let landed = grounded.grounded && !grounded.prev_grounded;
+            let fast_enough = speed >= slide.min_start_speed;
+            if crouch.crouching && on_ground && fast_enough && (crouched_now || landed) {
+                slide.sliding = true;
+                slide.time_sliding = 0.0;
+                kcc.velocity += horizontal / speed * slide.start_boost;
+            }
+            continue;
+        }
+
+        if !crouch.crouching || !on_ground {
+            slide.sliding = false;
+            continue;
+        }
+
+        slide.time_sliding += delta_seconds;
+
+        // Accelerate along the horizontal part of the floor's downhill direction.
+        let floor_normal = floor_detection.floor_normal.try_normalize().unwrap_or(up);
+        let slope_angle = floor_normal.angle_between(up);
+        let downhill = gravity.0.reject_from(floor_normal).reject_from(up).normalize_or_zero();
+        let slope_acceleration = slide.downhill_acceleration.sample(slope_angle);
+        kcc.velocity += downhill * slope_acceleration * delta_seconds;
+
+        let surface_friction =
+            floor_surface.map_or(1.0, |floor_surface| floor_surface.0.friction_multiplier());
+        let friction =
+            settings.friction * surface_friction * slide.friction.sample(slide.time_sliding);
+        kcc.velocity = acceleration::apply_friction(kcc.velocity, up, friction, 0.0, delta_seconds);
+
+        let vertical = kcc.velocity.project_onto(up);
+        let horizontal = (kcc.velocity - vertical).clamp_length_max(slide.max_speed);
+        kcc.velocity = vertical + horizontal;
+
+        if horizontal.length() < slide.min_speed {
+            slide.sliding = false;
+        }
+    }
+}
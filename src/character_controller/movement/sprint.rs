@@ -0,0 +1,145 @@
+//! Sprinting and stamina for the character controller.
+//!
+//! Sprinting raises the character's top speed while draining its [`Stamina`]. Once stamina runs
+//! out the character is exhausted and can't sprint until it has recovered, which only starts after
+//! a short delay. Exhaustion and recovery are sent as events so the HUD and audio can react.
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use super::{
+    super::{
+        input::PlayerActions,
+        CharacterControllerSet,
+    },
+    align_to_gravity,
+    movement_input,
+    Crouch,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_event::<StaminaExhausted>();
+    app.add_event::<StaminaRecovered>();
+    app.add_systems(
+        FixedUpdate,
+        sprint_system
+            .after(align_to_gravity)
+            .before(movement_input)
+            .in_set(CharacterControllerSet::Input),
+    );
+}
+
+/// Component that lets a character sprint.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Sprint {
+    /// How much the character's max speed is scaled by while sprinting.
+    pub speed_multiplier: f32,
+    /// Is the character currently sprinting?
+    pub sprinting: bool,
+}
+
+impl Default for Sprint {
+    fn default() -> Self {
+        Self { speed_multiplier: 1.6, sprinting: false }
+    }
+}
+
+/// Component that tracks how much longer a character can sprint for.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Stamina {
+    /// The current amount of stamina.
+    pub current: f32,
+    /// The maximum amount of stamina.
+    pub max: f32,
+    /// How much stamina is drained per second while sprinting.
+    pub drain_rate: f32,
+    /// How much stamina is regained per second once regeneration starts.
+    pub regen_rate: f32,
+    /// How long, in seconds, after the last sprint before stamina starts regenerating.
+    pub regen_delay: f32,
+    /// Seconds since stamina was last drained.
+    pub time_since_drained: f32,
+    /// The fraction of `max` an exhausted character has to regain before it can sprint again.
+    pub recovery_threshold: f32,
+    /// Is the character exhausted?
+    pub exhausted: bool,
+}
+
+impl Default for Stamina {
+    fn default() -> Self {
+        Self {
+            current: 100.0,
+            max: 100.0,
+            drain_rate: 20.0,
+            regen_rate: 25.0,
+            regen_delay: 1.0,
+            time_since_drained: 0.0,
+            recovery_threshold: 0.3,
+            exhausted: false,
+        }
+    }
+}
+
+/// Event sent when a character runs out of stamina.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct StaminaExhausted {
+    /// The character that is exhausted.
+    pub entity: Entity,
+}
+
+/// Event sent when an exhausted character has recovered enough stamina to sprint again.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct StaminaRecovered {
+    /// The character that recovered.
+    pub entity: Entity,
+}
+
+/// Starts and stops sprinting based on input, and drains or regenerates stamina.
+///
+/// Characters sprint while [`PlayerActions::Sprint`] is held, they are moving, they aren't crouched
+/// and they aren't exhausted.
+pub fn sprint_system(
+    mut query: Query<(
+        Entity,
+        &ActionState<PlayerActions>,
+        &mut Sprint,
+        &mut Stamina,
+        Option<&Crouch>,
+    )>,
+    mut exhausted_events: EventWriter<StaminaExhausted>,
+    mut recovered_events: EventWriter<StaminaRecovered>,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_seconds();
+    for (entity, action_state, mut sprint, mut stamina, crouch) in &mut query {
+        let moving = action_state.clamped_axis_pair(&PlayerActions::Movement).xy() != Vec2::ZERO;
+        let crouching = crouch.is_some_and(|crouch| crouch.crouching);
+        sprint.sprinting = action_state.pressed(&PlayerActions::Sprint)
+            && moving
+            && !crouching
+            && !stamina.exhausted;
+
+        if sprint.sprinting {
+            stamina.current = (stamina.current - stamina.drain_rate * delta_seconds).max(0.0);
+            stamina.time_since_drained = 0.0;
+            if stamina.current == 0.0 {
+                stamina.exhausted = true;
+                sprint.sprinting = false;
+                exhausted_events.send(StaminaExhausted { entity });
+            }
+            continue;
+        }
+
+        stamina.time_since_drained += delta_seconds;
+        if stamina.time_since_drained >= stamina.regen_delay {
+            stamina.current =
+                (stamina.current + stamina.regen_rate * delta_seconds).min(stamina.max);
+        }
+        if stamina.exhausted && stamina.current >= stamina.max * stamina.recovery_threshold {
+            stamina.exhausted = false;
+            recovered_events.send(StaminaRecovered { entity });
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Swimming and water volumes for the character controller.
+//!
+//! A water volume is a collider tagged with [`WaterVolume`], usually from Blender through Blenvy.
+//! Like ladders, water volumes are turned into sensors that spatial queries ignore. Once a
+//! character's feet are deep enough, it swims: buoyancy replaces [`Gravity`], input moves it in 3D
+//! along the camera's pitch and it bobs at the surface. [`Swim`] exposes how deep the character and
+//! its camera are, so audio and post-processing can react.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use super::{
+    super::{
+        camera_rig::{
+            RiggedCamera,
+            TrackedEntity,
+        },
+        input::PlayerActions,
+        kinematic_controller::KinematicCharacterController,
+        CharacterControllerSet,
+    },
+    ability::apply_ability_motion,
+    acceleration,
+    align_to_gravity,
+    gravity_system,
+    movement_input,
+    prepare_volumes,
+    Jump,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<WaterVolume>();
+    app.add_systems(
+        FixedUpdate,
+        (
+            prepare_volumes::<WaterVolume>.before(CharacterControllerSet::Input),
+            update_submersion
+                .after(align_to_gravity)
+                .before(movement_input)
+                .in_set(CharacterControllerSet::Input),
+            swim_system
+                .after(gravity_system)
+                .before(apply_ability_motion)
+                .in_set(CharacterControllerSet::Input),
+        ),
+    );
+}
+
+/// Component for volumes of water. It is registered for reflection, so it can be added to objects
+/// in Blender through Blenvy. The top of the volume is the water's surface.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct WaterVolume {
+    /// How strongly the water pulls a swimming character towards its floating depth.
+    pub buoyancy: f32,
+    /// How quickly the water slows a swimming character down.
+    pub drag: f32,
+}
+
+impl Default for WaterVolume {
+    fn default() -> Self {
+        Self { buoyancy: 8.0, drag: 2.0 }
+    }
+}
+
+/// Component that describes how a character swims, and tracks how submerged it is.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct Swim {
+    /// The top speed of a swimming character.
+    pub speed: f32,
+    /// How quickly a swimming character reaches its top speed.
+    pub acceleration: f32,
+    /// The character starts swimming once its feet are this deep.
+    pub swim_depth: f32,
+    /// The depth of the character's feet it floats at when left alone.
+    pub float_depth: f32,
+    /// How far the character bobs up and down while floating at the surface.
+    pub bob_amplitude: f32,
+    /// How many times per second the character bobs while floating at the surface.
+    pub bob_frequency: f32,
+    /// The upward speed of a jump out of the water.
+    pub exit_jump_speed: f32,
+    /// Is the character currently swimming?
+    pub swimming: bool,
+    /// How deep below the surface the character's feet are, `0.0` when out of the water.
+    pub depth: f32,
+    /// How deep below the surface the character's camera is, negative when it is above it.
+    pub camera_depth: f32,
+    /// The buoyancy of the water the character is in.
+    pub buoyancy: f32,
+    /// The drag of the water the character is in.
+    pub drag: f32,
+}
+
+impl Default for Swim {
+    fn default() -> Self {
+        Self {
+            speed: 4.0,
+            acceleration: 6.0,
+            swim_depth: 1.0,
+            float_depth: 1.1,
+            bob_amplitude: 0.05,
+            bob_frequency: 0.5,
+            exit_jump_speed: 5.0,
+            swimming: false,
+            depth: 0.0,
+            camera_depth: f32::NEG_INFINITY,
+            buoyancy: 0.0,
+            drag: 0.0,
+        }
+    }
+}
+
+impl Swim {
+    /// Is the character's camera under water?
+    pub fn camera_submerged(&self) -> bool {
+        self.camera_depth > 0.0
+    }
+}
+
+/// The lowest point of a character's collider along its up vector.
+pub fn character_feet(kcc: &KinematicCharacterController, transform: &Transform) -> Vec3 {
+    let aabb = kcc.collider.aabb(transform.translation, transform.rotation);
+    let half_height = (aabb.max - aabb.min).dot(kcc.up.abs()) * 0.5;
+    transform.translation - kcc.up * half_height
+}
+
+/// Measures how deep each character is in water, and starts and stops swimming.
+pub fn update_submersion(
+    mut query: Query<(
+        &mut Swim,
+        &KinematicCharacterController,
+        &Transform,
+        Option<&TrackedEntity>,
+    )>,
+    water: Query<(&WaterVolume, &Collider, &Position, &Rotation)>,
+) {
+    for (mut swim, kcc, transform, tracked_entity) in &mut query {
+        let up = kcc.up;
+        let feet = character_feet(kcc, transform);
+        let camera = transform.translation
+            + transform.rotation * tracked_entity.map_or(Vec3::ZERO, |tracked| tracked.0);
+
+        let volume = water.iter().find(|(_, collider, position, rotation)| {
+            collider.contains_point(**position, **rotation, feet)
+        });
+        let Some((volume, collider, position, rotation)) = volume else {
+            swim.swimming = false;
+            swim.depth = 0.0;
+            swim.camera_depth = f32::NEG_INFINITY;
+            continue;
+        };
+
+        // The surface is the highest point of the volume along our up vector.
+        let bounds = collider.aabb(position.0, *rotation);
+        let surface = (0..8)
+            .map(|corner| {
+                Vec3::new(
+                    if corner & 1 == 0 { bounds.min.x } else { bounds.max.x },
+                    if corner & 2 == 0 { bounds.min.y } else { bounds.max.y },
+                    if corner & 4 == 0 { bounds.min.z } else { bounds.max.z },
+                )
+                .dot(up)
+            })
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        swim.depth = surface - feet.dot(up);
+        swim.camera_depth = surface - camera.dot(up);
+        swim.swimming = swim.depth >= swim.swim_depth;
+        swim.buoyancy = volume.buoyancy;
+        swim.drag = volume.drag;
+    }
+}
+
+/// Moves swimming characters.
+///
+/// Forward input swims where the camera looks, jump swims up and crouch swims down. Without
+/// vertical input the character floats towards its floating depth, bobbing slightly. Jumping with
+/// the camera above the surface leaps out of the water.
+pub fn swim_system(
+    mut query: Query<(
+        &ActionState<PlayerActions>,
+        &Swim,
+        &mut KinematicCharacterController,
+        &Transform,
+        Option<&mut Jump>,
+    )>,
+    camera: Query<&Transform, With<RiggedCamera>>,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_seconds();
+    let camera_rotation = camera.get_single().map(|camera| camera.rotation).ok();
+    for (action_state, swim, mut kcc, transform, mut jump) in &mut query {
+        if !swim.swimming {
+            continue;
+        }
+        let up = kcc.up;
+
+        // Only a character with its head above water can leap out.
+        let at_surface = !swim.camera_submerged();
+        if let Some(jump) =
+            jump.as_mut().filter(|jump| at_surface && jump.time_since_pressed <= jump.buffer_time)
+        {
+            kcc.velocity = kcc.velocity.reject_from(up) + up * swim.exit_jump_speed;
+            jump.time_since_pressed = f32::INFINITY;
+            jump.jumping = true;
+            continue;
+        }
+
+        let movement = action_state.clamped_axis_pair(&PlayerActions::Movement).xy();
+        let rotation = camera_rotation.unwrap_or(transform.rotation);
+        let mut wish = rotation * Vec3::new(movement.x, 0.0, -movement.y);
+        let vertical_input = match (
+            action_state.pressed(&PlayerActions::Jump),
+            action_state.pressed(&PlayerActions::Crouch),
+        ) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        };
+        wish += up * vertical_input;
+        let wish_speed = swim.speed * wish.length().min(1.0);
+        kcc.velocity = acceleration::accelerate(
+            kcc.velocity,
+            wish.normalize_or_zero(),
+            wish_speed,
+            wish_speed,
+            swim.acceleration,
+            delta_seconds,
+        );
+
+        // Float towards the surface, unless the player is deliberately swimming up or down.
+        if vertical_input == 0.0 {
+            let bob = (time.elapsed_seconds() * swim.bob_frequency * std::f32::consts::TAU).sin()
+                * swim.bob_amplitude;
+            let offset = (swim.depth - (swim.float_depth + bob)).clamp(-1.0, 1.0);
+            kcc.velocity += up * offset * swim.buoyancy * delta_seconds;
+        }
+
+        kcc.velocity *= (1.0 - swim.drag * delta_seconds).max(0.0);
+    }
+}
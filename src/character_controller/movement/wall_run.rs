@@ -0,0 +1,183 @@
+//! Wall running and wall jumping for the character controller.
+//!
+//! An airborne character that moves along a wall marked with [`WallRunnable`] sticks to it for a
+//! limited time, with reduced gravity and the camera rolled away from the wall. Jumping during a
+//! wall run pushes the character off the wall along its normal.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use super::{
+    super::{
+        input::PlayerActions,
+        kinematic_controller::{
+            collider_body,
+            KCCGrounded,
+            KCCWallDetection,
+            KinematicCharacterController,
+        },
+        CharacterControllerSet,
+    },
+    jump::jump_system,
+    movement_input,
+    Gravity,
+    Jump,
+};
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<WallRunnable>();
+    app.add_systems(
+        FixedUpdate,
+        wall_run_system
+            .after(jump_system)
+            .before(movement_input)
+            .in_set(CharacterControllerSet::Input),
+    );
+}
+
+/// Marker component for surfaces that characters can run along. It is registered for reflection,
+/// so it can be added to objects in Blender through Blenvy.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
+pub struct WallRunnable;
+
+/// Component that describes how a character runs along walls, and tracks its wall run state.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component)]
+pub struct WallRun {
+    /// Gravity is scaled by this while wall running.
+    pub gravity_multiplier: f32,
+    /// The longest a single wall run can last, in seconds. The character has to touch the ground
+    /// or wall jump before it can wall run again.
+    pub max_duration: f32,
+    /// The speed along the wall the character needs to start and keep wall running.
+    pub min_speed: f32,
+    /// The speed the character is pulled into the wall with, so it keeps touching it.
+    pub stick_speed: f32,
+    /// The speed a wall jump pushes the character away from the wall with.
+    pub jump_away_speed: f32,
+    /// How far the camera rolls away from the wall, in radians.
+    pub camera_roll: f32,
+    /// How quickly the camera rolls towards its target, higher is faster.
+    pub camera_roll_speed: f32,
+    /// Is the character currently wall running?
+    pub running: bool,
+    /// The normal of the wall the character is running along.
+    pub wall_normal: Vec3,
+    /// Seconds spent wall running since the character last touched the ground or wall jumped.
+    pub time_running: f32,
+}
+
+impl Default for WallRun {
+    fn default() -> Self {
+        Self {
+            gravity_multiplier: 0.2,
+            max_duration: 1.5,
+            min_speed: 4.0,
+            stick_speed: 1.0,
+            jump_away_speed: 6.0,
+            camera_roll: 0.25,
+            camera_roll_speed: 8.0,
+            running: false,
+            wall_normal: Vec3::ZERO,
+            time_running: 0.0,
+        }
+    }
+}
+
+impl WallRun {
+    /// The camera roll, in radians, for a character whose right vector is `right`.
+    pub fn target_roll(&self, right: Vec3) -> f32 {
+        if !self.running {
+            return 0.0;
+        }
+        let side = self.wall_normal.dot(right);
+        if side.abs() < 0.01 {
+            return 0.0;
+        }
+        -self.camera_roll * side.signum()
+    }
+}
+
+/// Starts, updates and ends wall runs, and performs wall jumps.
+///
+/// A wall run starts when an airborne character moves forward along a [`WallRunnable`] wall fast
+/// enough, and ends when it lets go of forward, slows down, loses the wall, lands or runs out of
+/// time. A wall jump uses a buffered jump press, so it shares [`Jump`]'s timing.
+pub fn wall_run_system(
+    mut query: Query<(
+        &ActionState<PlayerActions>,
+        &mut WallRun,
+        &mut KinematicCharacterController,
+        &KCCGrounded,
+        &KCCWallDetection,
+        &Gravity,
+        Option<&mut Jump>,
+    )>,
+    runnable: Query<(), With<WallRunnable>>,
+    collider_parents: Query<&ColliderParent>,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_seconds();
+    for (action_state, mut wall_run, mut kcc, grounded, wall_detection, gravity, mut jump) in
+        &mut query
+    {
+        if grounded.grounded {
+            wall_run.running = false;
+            wall_run.time_running = 0.0;
+            continue;
+        }
+
+        let up = kcc.up;
+        let wall_normal = wall_detection.wall_entity.zip(wall_detection.wall_normal).and_then(
+            |(wall, normal)| {
+                let is_runnable = runnable.contains(wall)
+                    || runnable.contains(collider_body(wall, &collider_parents));
+                is_runnable.then_some(normal.reject_from(up).normalize_or_zero())
+            },
+        );
+        // While running, we keep touching the wall from last tick's pull towards it.
+        let Some(wall_normal) = wall_normal.filter(|normal| *normal != Vec3::ZERO) else {
+            wall_run.running = false;
+            continue;
+        };
+
+        let along_wall = kcc.velocity.reject_from(up).reject_from(wall_normal);
+        let moving_forward = action_state.clamped_axis_pair(&PlayerActions::Movement).y > 0.0;
+        let can_run = moving_forward
+            && along_wall.length() >= wall_run.min_speed
+            && wall_run.time_running < wall_run.max_duration;
+        if !can_run {
+            wall_run.running = false;
+            continue;
+        }
+
+        if !wall_run.running {
+            // Catch the character, so a wall run started while falling doesn't keep falling fast.
+            let vertical_speed = kcc.velocity.dot(up);
+            if vertical_speed < 0.0 {
+                kcc.velocity -= up * vertical_speed;
+            }
+            wall_run.running = true;
+        }
+        wall_run.wall_normal = wall_normal;
+        wall_run.time_running += delta_seconds;
+
+        if let Some(jump) = jump.as_mut().filter(|jump| jump.time_since_pressed <= jump.buffer_time)
+        {
+            let jump_velocity = jump.velocity(gravity.0.length());
+            kcc.velocity = along_wall + wall_normal * wall_run.jump_away_speed + up * jump_velocity;
+            jump.time_since_pressed = f32::INFINITY;
+            jump.jumping = true;
+            // Let the character run along the next wall it reaches.
+            wall_run.running = false;
+            wall_run.time_running = 0.0;
+            continue;
+        }
+
+        // Replace any motion towards or away from the wall with a gentle pull into it.
+        let vertical = kcc.velocity.project_onto(up);
+        kcc.velocity = along_wall + vertical - wall_normal * wall_run.stick_speed;
+    }
+}
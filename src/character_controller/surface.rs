@@ -0,0 +1,162 @@
+//! Surface materials for level geometry.
+//!
+//! A [`SurfaceMaterial`] on a collider, or on the rigid body it belongs to, changes how characters
+//! move on it, which footsteps they make and what effect bullets leave behind. It is registered for
+//! reflection, so designers can assign it in Blender through Blenvy. Surfaces without one behave
+//! like concrete.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use super::{
+    kinematic_controller::{
+        collider_body,
+        KCCFloorDetection,
+    },
+    movement::movement_input,
+    CharacterControllerSet,
+};
+use crate::assets::SfxHandles;
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<SurfaceMaterial>();
+    app.register_type::<SoftSurface>();
+    app.add_systems(
+        FixedUpdate,
+        update_floor_surface.before(movement_input).in_set(CharacterControllerSet::Input),
+    );
+}
+
+/// The material a surface is made of.
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[reflect(Component)]
+pub enum SurfaceMaterial {
+    #[default]
+    Concrete,
+    Metal,
+    Ice,
+    Mud,
+}
+
+/// The effect a bullet leaves where it hits a surface.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImpactEffect {
+    /// A puff of dust and chips of stone.
+    Dust,
+    /// A shower of sparks.
+    Sparks,
+    /// Splinters of ice.
+    Shards,
+    /// A wet splat.
+    Splat,
+}
+
+impl SurfaceMaterial {
+    /// Scales ground friction. Low values make the surface slippery.
+    pub fn friction_multiplier(self) -> f32 {
+        match self {
+            Self::Concrete => 1.0,
+            Self::Metal => 0.9,
+            Self::Ice => 0.1,
+            Self::Mud => 1.5,
+        }
+    }
+
+    /// Scales ground acceleration, so slippery surfaces are also hard to get going on.
+    pub fn acceleration_multiplier(self) -> f32 {
+        match self {
+            Self::Ice => 0.2,
+            _ => 1.0,
+        }
+    }
+
+    /// Scales the top speed of characters walking on the surface.
+    pub fn speed_multiplier(self) -> f32 {
+        match self {
+            Self::Mud => 0.6,
+            _ => 1.0,
+        }
+    }
+
+    /// The key in [`SfxHandles`] of the footstep sound effects for the surface. Surfaces whose
+    /// recordings aren't loaded fall back to [`SfxHandles::PATH_STEP`].
+    pub fn footstep_sfx(self) -> &'static str {
+        match self {
+            Self::Concrete => SfxHandles::PATH_STEP,
+            Self::Metal => SfxHandles::PATH_STEP_METAL,
+            Self::Ice => SfxHandles::PATH_STEP_ICE,
+            Self::Mud => SfxHandles::PATH_STEP_MUD,
+        }
+    }
+
+    /// The playback speed of footsteps on the surface, which also shifts their pitch.
+    pub fn footstep_speed(self) -> f32 {
+        match self {
+            Self::Concrete => 1.0,
+            Self::Metal => 1.25,
+            Self::Ice => 1.4,
+            Self::Mud => 0.75,
+        }
+    }
+
+    /// The effect a bullet leaves where it hits the surface.
+    pub fn impact_effect(self) -> ImpactEffect {
+        match self {
+            Self::Concrete => ImpactEffect::Dust,
+            Self::Metal => ImpactEffect::Sparks,
+            Self::Ice => ImpactEffect::Shards,
+            Self::Mud => ImpactEffect::Splat,
+        }
+    }
+
+    /// The material of the collider `entity`. Falls back to the material of the rigid body it
+    /// belongs to, and then to the default.
+    pub fn of(
+        entity: Entity,
+        materials: &Query<&SurfaceMaterial>,
+        collider_parents: &Query<&ColliderParent>,
+    ) -> Self {
+        materials
+            .get(entity)
+            .or_else(|_| materials.get(collider_body(entity, collider_parents)))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Marker component for soft surfaces, such as mattresses or hay, that cushion landings so they
+/// deal no fall damage. Like [`SurfaceMaterial`], it may be on the collider or its rigid body.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
+pub struct SoftSurface;
+
+impl SoftSurface {
+    /// Is the collider `entity`, or the rigid body it belongs to, soft?
+    pub fn is_soft(
+        entity: Entity,
+        soft_surfaces: &Query<(), With<SoftSurface>>,
+        collider_parents: &Query<&ColliderParent>,
+    ) -> bool {
+        soft_surfaces.contains(entity)
+            || soft_surfaces.contains(collider_body(entity, collider_parents))
+    }
+}
+
+/// Component that holds the material of the floor a character is standing on.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component)]
+pub struct FloorSurface(pub SurfaceMaterial);
+
+/// Looks up the material of the floor each character is standing on.
+pub fn update_floor_surface(
+    mut query: Query<(&KCCFloorDetection, &mut FloorSurface)>,
+    materials: Query<&SurfaceMaterial>,
+    collider_parents: Query<&ColliderParent>,
+) {
+    for (floor_detection, mut floor_surface) in &mut query {
+        floor_surface.0 = floor_detection
+            .floor_entity
+            .map(|floor| SurfaceMaterial::of(floor, &materials, &collider_parents))
+            .unwrap_or_default();
+    }
+}
@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+
+use super::*;
+use crate::character_controller::{
+    movement::WaterVolume,
+    surface::SoftSurface,
+    weapon::{
+        DamageEvent,
+        DamageSource,
+    },
+};
+
+/// Drops a character from `height` onto a floor with `floor` added to it, and returns the damage
+/// it took.
+fn drop_from(height: f32, floor: impl Bundle) -> Vec<DamageEvent> {
+    let mut app = test_app();
+    log_events::<DamageEvent>(&mut app);
+    drop_onto_floor(&mut app, height, floor);
+    logged_events::<DamageEvent>(&app).to_vec()
+}
+
+#[test]
+fn short_drop_is_safe() {
+    assert!(drop_from(2.0, ()).is_empty());
+}
+
+#[test]
+fn long_drop_deals_fall_damage() {
+    let damage = drop_from(10.0, ());
+    assert_eq!(damage.len(), 1);
+    assert!(damage[0].amount > 0.0);
+    assert_eq!(damage[0].source, DamageSource::Fall);
+    assert_eq!(damage[0].source.to_string(), "the fall");
+}
+
+#[test]
+fn soft_surface_cancels_fall_damage() {
+    assert!(drop_from(10.0, SoftSurface).is_empty());
+}
+
+#[test]
+fn landing_in_shallow_water_cancels_fall_damage() {
+    let mut app = test_app();
+    log_events::<DamageEvent>(&mut app);
+    // A pool only half a meter deep, too shallow to swim in or to slow the fall.
+    let water = spawn_box(&mut app, Transform::from_xyz(0.0, 0.0, 0.0), Vec3::new(10.0, 1.0, 10.0));
+    app.world_mut().entity_mut(water).insert(WaterVolume::default());
+
+    drop_onto_floor(&mut app, 10.0, ());
+
+    assert!(logged_events::<DamageEvent>(&app).is_empty());
+}
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+
+use super::*;
+use crate::character_controller::{
+    footsteps::{
+        Footstep,
+        Footsteps,
+    },
+    surface::SurfaceMaterial,
+};
+
+fn footsteps(app: &App) -> &[Footstep] {
+    logged_events::<Footstep>(app)
+}
+
+#[test]
+fn steps_once_per_stride() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    log_events::<Footstep>(&mut app);
+    let start = translation(&app, character);
+
+    set_input(&mut app, ScriptedInput { movement: Vec2::Y, ..default() });
+    step(&mut app, 128);
+
+    let stride = app.world().get::<Footsteps>(character).unwrap().walk_stride;
+    let walked = translation(&app, character).distance(start);
+    let expected = (walked / stride).floor() as usize;
+    let steps = footsteps(&app).len();
+    assert!(
+        steps.abs_diff(expected) <= 1,
+        "walking {walked} units should take about {expected} steps, but took {steps}"
+    );
+}
+
+/// Drops a character from `height` onto a floor made of `surface`, and returns the footsteps it
+/// made landing.
+fn land_from(height: f32, surface: SurfaceMaterial) -> Vec<Footstep> {
+    let mut app = test_app();
+    log_events::<Footstep>(&mut app);
+    drop_onto_floor(&mut app, height, surface);
+    footsteps(&app).to_vec()
+}
+
+#[test]
+fn hard_landing_makes_a_landing_step() {
+    let steps = land_from(2.0, SurfaceMaterial::Ice);
+    let landing_volume = Footsteps::default().landing_volume;
+    assert_eq!(steps.len(), 1);
+    assert_eq!(steps[0].volume, landing_volume);
+    assert_eq!(steps[0].surface, SurfaceMaterial::Ice);
+}
+
+#[test]
+fn soft_landing_is_silent() {
+    assert!(land_from(0.02, SurfaceMaterial::Concrete).is_empty());
+}
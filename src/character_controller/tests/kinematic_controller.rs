@@ -0,0 +1,363 @@
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use super::*;
+use crate::character_controller::kinematic_controller::{
+    KCCImpulse,
+    KCCKnockbackResistance,
+    KCCLanded,
+    KCCUnpushable,
+    KinematicCharacterController,
+};
+
+/// Walk slowly, so that the scripted geometry stays within reach for the whole test.
+const WALK: Vec2 = Vec2::new(0.0, 0.25);
+
+fn walk_forward(app: &mut App, ticks: u32) {
+    set_input(app, ScriptedInput { movement: WALK, ..default() });
+    step(app, ticks);
+}
+
+#[test]
+fn falls_and_lands_on_floor() {
+    let mut app = test_app();
+    spawn_floor(&mut app);
+    let character = spawn_character(&mut app, Vec3::Y * 2.0);
+
+    step(&mut app, 128);
+
+    assert!(grounded(&app, character));
+    let feet = translation(&app, character).y - 0.8;
+    assert!(feet.abs() < 0.05, "feet should rest on the floor, but are at {feet}");
+}
+
+#[test]
+fn walks_in_input_direction() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+
+    walk_forward(&mut app, 64);
+
+    let translation = translation(&app, character);
+    assert!(translation.z < -1.0, "character should have walked forward, but is at {translation}");
+    assert!(translation.x.abs() < 0.01);
+    assert!(grounded(&app, character));
+}
+
+#[test]
+fn thin_wall_blocks_movement() {
+    let mut app = test_app();
+    spawn_wall(&mut app, 1.0, 0.05);
+    let character = standing_character(&mut app);
+
+    walk_forward(&mut app, 256);
+
+    let z = translation(&app, character).z;
+    assert!(z > -1.0, "character should be stopped by the wall, but is at z = {z}");
+}
+
+#[test]
+fn fast_character_does_not_tunnel_through_thin_wall() {
+    let mut app = test_app();
+    spawn_wall(&mut app, 1.0, 0.05);
+    let character = standing_character(&mut app);
+
+    // Far more than the capsule is thick in a single tick.
+    app.world_mut().get_mut::<KinematicCharacterController>(character).unwrap().velocity =
+        Vec3::NEG_Z * 200.0;
+    step(&mut app, 8);
+
+    let z = translation(&app, character).z;
+    assert!(z > -1.0, "character should be stopped by the wall, but is at z = {z}");
+}
+
+#[test]
+fn climbs_stairs() {
+    let mut app = test_app();
+    spawn_stairs(&mut app, 0.2, 0.4, 5);
+    let character = standing_character(&mut app);
+
+    walk_forward(&mut app, 320);
+
+    let feet = translation(&app, character).y - 0.8;
+    assert!(feet > 0.9, "character should have climbed the stairs, but its feet are at {feet}");
+    assert!(grounded(&app, character));
+}
+
+#[test]
+fn stepping_is_what_climbs_stairs() {
+    let mut app = test_app();
+    spawn_stairs(&mut app, 0.2, 0.4, 5);
+    let character = standing_character(&mut app);
+    app.world_mut().get_mut::<KinematicCharacterController>(character).unwrap().max_step_height =
+        0.0;
+
+    walk_forward(&mut app, 320);
+
+    let feet = translation(&app, character).y - 0.8;
+    assert!(
+        feet < 0.1,
+        "character should not climb stairs without stepping, but its feet are at {feet}"
+    );
+}
+
+#[test]
+fn walks_up_shallow_ramp() {
+    let mut app = test_app();
+    spawn_ramp(&mut app, 20.0);
+    let character = standing_character(&mut app);
+
+    walk_forward(&mut app, 256);
+
+    let feet = translation(&app, character).y - 0.8;
+    assert!(feet > 0.5, "character should have walked up the ramp, but its feet are at {feet}");
+    assert!(grounded(&app, character));
+}
+
+#[test]
+fn cannot_walk_up_steep_ramp() {
+    let mut app = test_app();
+    spawn_ramp(&mut app, 60.0);
+    let character = standing_character(&mut app);
+
+    walk_forward(&mut app, 256);
+
+    let feet = translation(&app, character).y - 0.8;
+    assert!(feet < 0.5, "character should not climb a steep ramp, but its feet are at {feet}");
+}
+
+#[test]
+fn depenetrates_out_of_corner() {
+    let mut app = test_app();
+    spawn_floor(&mut app);
+    spawn_wall(&mut app, 0.0, 1.0);
+    spawn_box(&mut app, Transform::from_xyz(-0.5, 1.5, 0.0), Vec3::new(1.0, 3.0, 6.0));
+    // Start overlapping both walls of the corner.
+    let character = spawn_character(&mut app, Vec3::new(-0.2, 0.0, -0.2));
+
+    step(&mut app, 4);
+
+    let translation = translation(&app, character);
+    assert!(
+        translation.x >= 0.39,
+        "character should be pushed out of the side wall: {translation}"
+    );
+    assert!(
+        translation.z >= 0.39,
+        "character should be pushed out of the back wall: {translation}"
+    );
+}
+
+#[test]
+fn jump_leaves_and_returns_to_ground() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    assert!(grounded(&app, character));
+
+    set_input(&mut app, ScriptedInput { jump: true, ..default() });
+    step(&mut app, 2);
+    set_input(&mut app, ScriptedInput::default());
+    step(&mut app, 8);
+    assert!(!grounded(&app, character));
+
+    app.world_mut().resource_mut::<Events<KCCLanded>>().clear();
+    step(&mut app, 128);
+    assert!(grounded(&app, character));
+    assert!(!app.world().resource::<Events<KCCLanded>>().is_empty());
+}
+
+/// Jumps while holding `movement`, and returns how high the character rose.
+fn jump_height(app: &mut App, character: Entity, movement: Vec2) -> f32 {
+    let start = translation(app, character).y;
+    let mut peak = start;
+    set_input(app, ScriptedInput { movement, jump: true, ..default() });
+    step(app, 2);
+    set_input(app, ScriptedInput { movement, ..default() });
+    for _ in 0..64 {
+        step(app, 1);
+        peak = peak.max(translation(app, character).y);
+    }
+    peak - start
+}
+
+#[test]
+fn steep_ramp_keeps_jump_height() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    let flat_height = jump_height(&mut app, character, Vec2::ZERO);
+
+    let mut app = test_app();
+    spawn_ramp(&mut app, 60.0);
+    let character = standing_character(&mut app);
+    // Press up against the foot of the ramp before jumping.
+    walk_forward(&mut app, 64);
+    let ramp_height = jump_height(&mut app, character, WALK);
+
+    assert!(
+        ramp_height > flat_height - 0.05,
+        "jumping next to a steep ramp should keep the jump height of {flat_height}, but only rose \
+         {ramp_height}"
+    );
+}
+
+/// Spawns a light dynamic crate resting on the floor in front of the character.
+fn spawn_crate(app: &mut App) -> Entity {
+    const SIZE: f32 = 0.6;
+
+    app.world_mut()
+        .spawn((
+            RigidBody::Dynamic,
+            Collider::cuboid(SIZE, SIZE, SIZE),
+            TransformBundle::from_transform(Transform::from_xyz(0.0, SIZE * 0.5, -1.5)),
+        ))
+        .id()
+}
+
+fn crate_translation(app: &App, crate_entity: Entity) -> Vec3 {
+    app.world().get::<Position>(crate_entity).unwrap().0
+}
+
+#[test]
+fn walking_into_crate_pushes_it() {
+    let mut app = test_app();
+    let crate_entity = spawn_crate(&mut app);
+    let _character = standing_character(&mut app);
+    let start = crate_translation(&app, crate_entity);
+
+    walk_forward(&mut app, 128);
+
+    let moved = crate_translation(&app, crate_entity) - start;
+    assert!(moved.z < -0.3, "character should push the crate forward, but it moved {moved}");
+}
+
+#[test]
+fn unpushable_crate_stays_put() {
+    let mut app = test_app();
+    let crate_entity = spawn_crate(&mut app);
+    app.world_mut().entity_mut(crate_entity).insert(KCCUnpushable);
+    let character = standing_character(&mut app);
+    let start = crate_translation(&app, crate_entity);
+
+    walk_forward(&mut app, 128);
+
+    let moved = crate_translation(&app, crate_entity) - start;
+    assert!(moved.length() < 0.05, "unpushable crate should stay put, but it moved {moved}");
+    let translation = translation(&app, character);
+    assert!(translation.z > -1.2, "crate should block the character, but it is at {translation}");
+}
+
+/// Spawns a kinematic platform whose top is at `y = 0`, with a character standing on its center.
+fn character_on_platform(app: &mut App, linear_velocity: Vec3, angular_velocity: Vec3) -> Entity {
+    let platform = spawn_box(app, Transform::from_xyz(0.0, -0.5, 0.0), Vec3::new(10.0, 1.0, 10.0));
+    app.world_mut().entity_mut(platform).insert((
+        RigidBody::Kinematic,
+        LinearVelocity(linear_velocity),
+        AngularVelocity(angular_velocity),
+    ));
+    let character = spawn_character(app, Vec3::ZERO);
+    step(app, 4);
+    character
+}
+
+#[test]
+fn moving_platform_carries_character() {
+    let mut app = test_app();
+    let character = character_on_platform(&mut app, Vec3::X * 2.0, Vec3::ZERO);
+    let start = translation(&app, character);
+
+    step(&mut app, 64);
+
+    let moved = translation(&app, character) - start;
+    assert!(
+        (moved.x - 2.0).abs() < 0.2,
+        "platform should carry the character, but it moved {moved}"
+    );
+    assert!(grounded(&app, character));
+    let velocity = app.world().get::<KinematicCharacterController>(character).unwrap().velocity;
+    assert!(
+        velocity.x.abs() < 0.1,
+        "riding should not build up momentum, but moving at {velocity}"
+    );
+}
+
+#[test]
+fn jumping_off_platform_keeps_its_momentum() {
+    let mut app = test_app();
+    let character = character_on_platform(&mut app, Vec3::X * 2.0, Vec3::ZERO);
+    step(&mut app, 16);
+
+    set_input(&mut app, ScriptedInput { jump: true, ..default() });
+    step(&mut app, 2);
+    set_input(&mut app, ScriptedInput::default());
+    step(&mut app, 8);
+
+    assert!(!grounded(&app, character));
+    let velocity = app.world().get::<KinematicCharacterController>(character).unwrap().velocity;
+    assert!(
+        (velocity.x - 2.0).abs() < 0.2,
+        "character should keep the platform's momentum, but is moving at {velocity}"
+    );
+}
+
+#[test]
+fn spinning_platform_turns_character() {
+    let mut app = test_app();
+    let character = character_on_platform(&mut app, Vec3::ZERO, Vec3::Y);
+    let start = app.world().get::<Transform>(character).unwrap().rotation;
+
+    step(&mut app, 64);
+
+    let rotation = app.world().get::<Transform>(character).unwrap().rotation;
+    let (yaw, _, _) = (start.inverse() * rotation).to_euler(EulerRot::YXZ);
+    assert!((yaw - 1.0).abs() < 0.1, "character should turn with the platform, but turned {yaw}");
+}
+
+#[test]
+fn upward_impulse_launches_character() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    let start = translation(&app, character);
+
+    app.world_mut()
+        .send_event(KCCImpulse { entity: character, impulse: Vec3::new(0.0, 6.0, -4.0) });
+    step(&mut app, 8);
+
+    assert!(!grounded(&app, character));
+    let translation = translation(&app, character);
+    assert!(
+        translation.y > start.y + 0.3,
+        "impulse should launch the character, but is at {translation}"
+    );
+    assert!(translation.z < start.z - 0.2);
+}
+
+/// Knocks a standing character back, and returns how much its horizontal velocity changed.
+fn knockback(resistance: Option<f32>) -> Vec3 {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    if let Some(resistance) = resistance {
+        app.world_mut().entity_mut(character).insert(KCCKnockbackResistance(resistance));
+    }
+    let velocity = |app: &App| {
+        app.world().get::<KinematicCharacterController>(character).unwrap().velocity.with_y(0.0)
+    };
+    let start = velocity(&app);
+
+    app.world_mut()
+        .send_event(KCCImpulse { entity: character, impulse: Vec3::new(0.0, 6.0, -4.0) });
+    step(&mut app, 1);
+
+    velocity(&app) - start
+}
+
+#[test]
+fn knockback_resistance_scales_impulses() {
+    let unresisted = knockback(None);
+    let resisted = knockback(Some(0.5));
+
+    assert!(unresisted.length() > 1.0, "impulse should knock the character back: {unresisted}");
+    assert!(
+        resisted.distance(unresisted * 0.5) < 0.01,
+        "half resistance should halve the knockback of {unresisted}, but it was {resisted}"
+    );
+}
@@ -0,0 +1,239 @@
+//! Headless test harness for the character controller.
+//!
+//! [`test_app`] builds an [`App`] with [`MinimalPlugins`], Avian and the character controller
+//! plugin, without a window or renderer. Every [`App::update`] advances time by exactly one fixed
+//! tick, so tests are deterministic. Input is scripted through the [`ScriptedInput`] resource,
+//! which is written to every character's [`ActionState<PlayerActions>`] at the start of each fixed
+//! tick.
+
+mod fall_damage;
+mod footsteps;
+mod kinematic_controller;
+mod movement;
+mod weapon;
+
+use std::time::Duration;
+
+use avian3d::prelude::*;
+use bevy::{
+    input::InputPlugin,
+    prelude::*,
+    scene::ScenePlugin,
+    state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+};
+use leafwing_input_manager::prelude::*;
+
+use super::{
+    input::PlayerActions,
+    kinematic_controller::KCCGrounded,
+    CharacterControllerBundle,
+    CharacterControllerSet,
+};
+use crate::GameState;
+
+/// The length of a single fixed tick, Bevy's default of 64 Hz.
+pub const TICK: Duration = Duration::from_micros(15_625);
+
+/// The input that is fed to every character on each fixed tick.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct ScriptedInput {
+    /// The movement axis, `y` is forward.
+    pub movement: Vec2,
+    /// Whether jump is held.
+    pub jump: bool,
+    /// Whether crouch is held.
+    pub crouch: bool,
+    /// Whether sprint is held.
+    pub sprint: bool,
+    /// Whether dash is held.
+    pub dash: bool,
+}
+
+/// Builds a headless app running the character controller in the [`GameState::Playing`] state.
+pub fn test_app() -> App {
+    test_app_with_frame_time(TICK)
+}
+
+/// Like [`test_app`], but every [`App::update`] advances time by `frame_time` instead of one fixed
+/// tick, so a single update may run any number of fixed ticks.
+pub fn test_app_with_frame_time(frame_time: Duration) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        HierarchyPlugin,
+        AssetPlugin::default(),
+        ScenePlugin,
+        InputPlugin,
+        StatesPlugin,
+        PhysicsPlugins::default(),
+    ))
+    .init_resource::<Assets<Mesh>>()
+    .insert_resource(Time::<Fixed>::from_duration(TICK))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(frame_time))
+    .insert_state(GameState::Playing)
+    .init_resource::<ScriptedInput>()
+    .add_plugins(super::plugin)
+    .add_systems(FixedUpdate, apply_scripted_input.before(CharacterControllerSet::Input));
+
+    // Let the first update run so time starts advancing in fixed ticks.
+    app.update();
+    app
+}
+
+fn apply_scripted_input(
+    input: Res<ScriptedInput>,
+    mut query: Query<&mut ActionState<PlayerActions>>,
+) {
+    for mut action_state in &mut query {
+        action_state.set_axis_pair(&PlayerActions::Movement, input.movement);
+        for (action, held) in [
+            (PlayerActions::Jump, input.jump),
+            (PlayerActions::Crouch, input.crouch),
+            (PlayerActions::Sprint, input.sprint),
+            (PlayerActions::Dash, input.dash),
+        ] {
+            if held {
+                action_state.press(&action);
+            } else {
+                action_state.release(&action);
+            }
+        }
+    }
+}
+
+/// Spawns a character whose feet are at `feet`. Its input map is removed so that only
+/// [`ScriptedInput`] drives it.
+pub fn spawn_character(app: &mut App, feet: Vec3) -> Entity {
+    let bundle = CharacterControllerBundle::default();
+    let half_height = bundle.crouch.radius + bundle.crouch.standing_length * 0.5;
+    app.world_mut()
+        .spawn((
+            bundle,
+            TransformBundle::from_transform(Transform::from_translation(
+                feet + Vec3::Y * (half_height + 0.01),
+            )),
+        ))
+        .remove::<InputMap<PlayerActions>>()
+        .id()
+}
+
+/// Spawns a floor and a character standing on it, and lets it settle.
+pub fn standing_character(app: &mut App) -> Entity {
+    spawn_floor(app);
+    let character = spawn_character(app, Vec3::ZERO);
+    step(app, 16);
+    character
+}
+
+/// Spawns a floor with `floor` added to it, drops a character onto it from `height` and lets it
+/// land.
+pub fn drop_onto_floor(app: &mut App, height: f32, floor: impl Bundle) -> Entity {
+    let floor_entity = spawn_floor(app);
+    app.world_mut().entity_mut(floor_entity).insert(floor);
+    let character = spawn_character(app, Vec3::Y * height);
+
+    step(app, 128);
+    assert!(grounded(app, character));
+    character
+}
+
+/// Spawns a static box with the given full `size`.
+pub fn spawn_box(app: &mut App, transform: Transform, size: Vec3) -> Entity {
+    app.world_mut()
+        .spawn((
+            RigidBody::Static,
+            Collider::cuboid(size.x, size.y, size.z),
+            TransformBundle::from_transform(transform),
+        ))
+        .id()
+}
+
+/// Spawns a large floor whose top is at `y = 0`.
+pub fn spawn_floor(app: &mut App) -> Entity {
+    spawn_box(app, Transform::from_xyz(0.0, -0.5, 0.0), Vec3::new(40.0, 1.0, 40.0))
+}
+
+/// Spawns a wall across the `-Z` direction, `distance` away from the origin.
+pub fn spawn_wall(app: &mut App, distance: f32, thickness: f32) -> Entity {
+    spawn_box(
+        app,
+        Transform::from_xyz(0.0, 1.5, -distance - thickness * 0.5),
+        Vec3::new(6.0, 3.0, thickness),
+    )
+}
+
+/// Spawns a ramp that starts at `z = -1` on the floor and rises towards `-Z` at `angle` degrees.
+pub fn spawn_ramp(app: &mut App, angle: f32) -> Entity {
+    const LENGTH: f32 = 10.0;
+    const THICKNESS: f32 = 0.2;
+
+    let rotation = Quat::from_rotation_x(angle.to_radians());
+    let half_extent = rotation * Vec3::new(0.0, THICKNESS * 0.5, -LENGTH * 0.5);
+    let center = Vec3::new(0.0, 0.0, -1.0) + half_extent;
+    spawn_box(
+        app,
+        Transform::from_translation(center).with_rotation(rotation),
+        Vec3::new(4.0, THICKNESS, LENGTH),
+    )
+}
+
+/// Spawns a staircase that starts at `z = -1` and climbs towards `-Z`.
+pub fn spawn_stairs(app: &mut App, step_height: f32, step_depth: f32, steps: u32) {
+    for step in 0..steps {
+        let height = step_height * (step + 1) as f32;
+        let z = -1.0 - step_depth * (step as f32 + 0.5);
+        spawn_box(
+            app,
+            Transform::from_xyz(0.0, height * 0.5, z),
+            Vec3::new(4.0, height, step_depth),
+        );
+    }
+}
+
+/// Every `E` sent since [`log_events`] was called.
+#[derive(Resource)]
+pub struct EventLog<E>(pub Vec<E>);
+
+impl<E> Default for EventLog<E> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+/// Starts recording every `E` into an [`EventLog`].
+pub fn log_events<E: Event + Copy>(app: &mut App) {
+    app.init_resource::<EventLog<E>>().add_systems(Last, record_events::<E>);
+}
+
+fn record_events<E: Event + Copy>(mut events: EventReader<E>, mut log: ResMut<EventLog<E>>) {
+    log.0.extend(events.read().copied());
+}
+
+/// The events recorded by [`log_events`] so far.
+pub fn logged_events<E: Event>(app: &App) -> &[E] {
+    &app.world().resource::<EventLog<E>>().0
+}
+
+/// Replaces the scripted input for all characters.
+pub fn set_input(app: &mut App, input: ScriptedInput) {
+    *app.world_mut().resource_mut::<ScriptedInput>() = input;
+}
+
+/// Advances the app by `ticks` fixed ticks.
+pub fn step(app: &mut App, ticks: u32) {
+    for _ in 0..ticks {
+        app.update();
+    }
+}
+
+/// The current translation of `entity`.
+pub fn translation(app: &App, entity: Entity) -> Vec3 {
+    app.world().get::<Transform>(entity).unwrap().translation
+}
+
+/// Whether `entity` is currently grounded.
+pub fn grounded(app: &App, entity: Entity) -> bool {
+    app.world().get::<KCCGrounded>(entity).unwrap().grounded
+}
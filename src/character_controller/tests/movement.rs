@@ -0,0 +1,445 @@
+use std::time::Duration;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use super::*;
+use crate::character_controller::{
+    kinematic_controller::{
+        KCCFloorDetection,
+        KinematicCharacterController,
+    },
+    movement::{
+        Climb,
+        Crouch,
+        Dash,
+        Ladder,
+        Mantle,
+        Slide,
+        Sprint,
+        Stamina,
+        Swim,
+        WallRun,
+        WallRunnable,
+        WaterVolume,
+    },
+    surface::SurfaceMaterial,
+};
+
+#[test]
+fn sprinting_is_faster_than_walking() {
+    let mut walk_app = test_app();
+    let walker = standing_character(&mut walk_app);
+    set_input(&mut walk_app, ScriptedInput { movement: Vec2::Y, ..default() });
+    step(&mut walk_app, 64);
+
+    let mut sprint_app = test_app();
+    let sprinter = standing_character(&mut sprint_app);
+    set_input(&mut sprint_app, ScriptedInput { movement: Vec2::Y, sprint: true, ..default() });
+    step(&mut sprint_app, 64);
+
+    let walked = -translation(&walk_app, walker).z;
+    let sprinted = -translation(&sprint_app, sprinter).z;
+    assert!(sprinted > walked * 1.2, "sprinted {sprinted} but walked {walked}");
+    assert!(sprint_app.world().get::<Stamina>(sprinter).unwrap().current < 100.0);
+}
+
+/// Counts the fixed ticks of [`scripted_run`].
+#[derive(Resource, Default)]
+struct ScriptTick(u32);
+
+/// Walks forward, jumps partway through, then lets go and comes to a stop. The input is keyed to
+/// fixed ticks, so it is the same no matter how many ticks each frame runs.
+fn scripted_run(mut tick: ResMut<ScriptTick>, mut input: ResMut<ScriptedInput>) {
+    *input = ScriptedInput {
+        movement: if tick.0 < 96 { Vec2::new(0.5, 1.0) } else { Vec2::ZERO },
+        jump: (32..34).contains(&tick.0),
+        ..default()
+    };
+    tick.0 += 1;
+}
+
+/// Runs [`scripted_run`] for `ticks` fixed ticks at the given frame rate, and returns where the
+/// character ended up.
+fn run_script_at(frame_rate: f64, ticks: u32) -> Vec3 {
+    let mut app = test_app_with_frame_time(Duration::from_secs_f64(1.0 / frame_rate));
+    app.init_resource::<ScriptTick>()
+        .add_systems(FixedUpdate, scripted_run.before(apply_scripted_input));
+    spawn_floor(&mut app);
+    let character = spawn_character(&mut app, Vec3::ZERO);
+
+    while app.world().resource::<ScriptTick>().0 < ticks {
+        app.update();
+    }
+    translation(&app, character)
+}
+
+#[test]
+fn movement_is_frame_rate_independent() {
+    // Long enough for the character to come to a full stop after the script lets go.
+    const TICKS: u32 = 256;
+
+    let reference = run_script_at(60.0, TICKS);
+    for frame_rate in [30.0, 144.0] {
+        let translation = run_script_at(frame_rate, TICKS);
+        assert!(
+            translation.distance(reference) < 0.01,
+            "running at {frame_rate} fps ended at {translation}, but 60 fps ended at {reference}"
+        );
+    }
+}
+
+#[test]
+fn air_strafing_gains_speed() {
+    let mut app = test_app();
+    let character = spawn_character(&mut app, Vec3::Y * 100.0);
+    app.world_mut().get_mut::<KinematicCharacterController>(character).unwrap().velocity =
+        Vec3::NEG_Z * 8.0;
+    set_input(&mut app, ScriptedInput { movement: Vec2::X, ..default() });
+
+    // Keep looking along the velocity while strafing, like turning the mouse during an air-strafe.
+    for _ in 0..32 {
+        let velocity = app.world().get::<KinematicCharacterController>(character).unwrap().velocity;
+        let forward = Dir3::new(velocity.with_y(0.0)).unwrap();
+        app.world_mut().get_mut::<Transform>(character).unwrap().look_to(forward, Dir3::Y);
+        step(&mut app, 1);
+    }
+
+    let velocity = app.world().get::<KinematicCharacterController>(character).unwrap().velocity;
+    let speed = velocity.with_y(0.0).length();
+    assert!(speed > 8.5, "air-strafing should gain horizontal speed, but is at {speed}");
+}
+
+#[test]
+fn sprinting_exhausts_and_stamina_recovers() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+
+    // Drain all stamina.
+    set_input(&mut app, ScriptedInput { movement: Vec2::Y, sprint: true, ..default() });
+    step(&mut app, 64 * 6);
+    let stamina = app.world().get::<Stamina>(character).unwrap();
+    assert!(stamina.exhausted);
+    assert!(!app.world().get::<Sprint>(character).unwrap().sprinting);
+
+    // Resting lets stamina regenerate after the delay.
+    set_input(&mut app, ScriptedInput::default());
+    step(&mut app, 64 * 3);
+    let stamina = app.world().get::<Stamina>(character).unwrap();
+    assert!(!stamina.exhausted);
+    assert!(stamina.current > 0.0);
+}
+
+fn vertical_speed(app: &App, character: Entity) -> f32 {
+    app.world().get::<KinematicCharacterController>(character).unwrap().velocity.y
+}
+
+#[test]
+fn coyote_time_allows_jumping_just_after_leaving_a_ledge() {
+    let mut app = test_app();
+    // A ledge that ends one meter ahead.
+    spawn_box(&mut app, Transform::from_xyz(0.0, -0.5, 0.0), Vec3::new(4.0, 1.0, 2.0));
+    let character = spawn_character(&mut app, Vec3::ZERO);
+    step(&mut app, 16);
+
+    set_input(&mut app, ScriptedInput { movement: Vec2::Y, ..default() });
+    for _ in 0..64 {
+        if !grounded(&app, character) {
+            break;
+        }
+        step(&mut app, 1);
+    }
+    assert!(!grounded(&app, character), "character should have walked off the ledge");
+    step(&mut app, 2);
+    assert!(vertical_speed(&app, character) <= 0.0);
+
+    set_input(&mut app, ScriptedInput { movement: Vec2::Y, jump: true, ..default() });
+    step(&mut app, 1);
+
+    let speed = vertical_speed(&app, character);
+    assert!(speed > 2.0, "a jump pressed during coyote time should fire, but rising at {speed}");
+}
+
+#[test]
+fn jump_pressed_before_landing_fires_on_touchdown() {
+    let mut app = test_app();
+    spawn_floor(&mut app);
+    let character = spawn_character(&mut app, Vec3::Y * 1.0);
+
+    // Fall until we're a couple of ticks away from the floor, then press jump.
+    let half_height = translation(&app, character).y - 1.01;
+    while translation(&app, character).y - half_height > 0.15 {
+        step(&mut app, 1);
+    }
+    assert!(!grounded(&app, character));
+    set_input(&mut app, ScriptedInput { jump: true, ..default() });
+
+    let mut fastest_rise = f32::NEG_INFINITY;
+    for _ in 0..12 {
+        step(&mut app, 1);
+        fastest_rise = fastest_rise.max(vertical_speed(&app, character));
+    }
+    assert!(fastest_rise > 3.0, "the buffered jump should fire, but only rose at {fastest_rise}");
+}
+
+#[test]
+fn holding_jump_does_not_bounce_again() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+
+    set_input(&mut app, ScriptedInput { jump: true, ..default() });
+    let mut takeoffs = 0;
+    let mut was_grounded = grounded(&app, character);
+    for _ in 0..192 {
+        step(&mut app, 1);
+        let is_grounded = grounded(&app, character);
+        if was_grounded && !is_grounded {
+            takeoffs += 1;
+        }
+        was_grounded = is_grounded;
+    }
+
+    assert_eq!(takeoffs, 1, "holding jump should only jump once");
+    assert!(grounded(&app, character));
+}
+
+/// Jumps, holding the button for `hold_ticks`, and returns how high the character rose.
+fn jump_peak(hold_ticks: u32) -> f32 {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    let start = translation(&app, character).y;
+
+    let mut peak = start;
+    for tick in 0..64 {
+        set_input(&mut app, ScriptedInput { jump: tick < hold_ticks, ..default() });
+        step(&mut app, 1);
+        peak = peak.max(translation(&app, character).y);
+    }
+    peak - start
+}
+
+#[test]
+fn releasing_jump_early_cuts_it_short() {
+    let tapped = jump_peak(2);
+    let held = jump_peak(64);
+    assert!(
+        tapped < held * 0.8,
+        "tapping jump should rise less than holding it, but rose {tapped} against {held}"
+    );
+}
+
+/// The heights of the character's own, sweep and floor detection colliders.
+fn collider_heights(app: &App, character: Entity) -> [f32; 3] {
+    let height = |collider: &Collider| {
+        let aabb = collider.aabb(Vec3::ZERO, Quat::IDENTITY);
+        aabb.max.y - aabb.min.y
+    };
+    let world = app.world();
+    [
+        height(world.get::<Collider>(character).unwrap()),
+        height(&world.get::<KinematicCharacterController>(character).unwrap().collider),
+        height(&world.get::<KCCFloorDetection>(character).unwrap().floor_collider),
+    ]
+}
+
+#[test]
+fn cannot_stand_up_under_a_low_ceiling() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    let standing = collider_heights(&app, character);
+    set_input(&mut app, ScriptedInput { crouch: true, ..default() });
+    step(&mut app, 8);
+    let crouched = collider_heights(&app, character);
+    assert!(crouched[0] < standing[0]);
+
+    // A ceiling that leaves room to crouch, but not to stand.
+    let ceiling = spawn_box(&mut app, Transform::from_xyz(0.0, 1.7, 0.0), Vec3::new(4.0, 1.0, 4.0));
+    step(&mut app, 2);
+    set_input(&mut app, ScriptedInput::default());
+    step(&mut app, 16);
+
+    assert!(app.world().get::<Crouch>(character).unwrap().crouching);
+    assert_eq!(collider_heights(&app, character), crouched);
+
+    app.world_mut().despawn(ceiling);
+    step(&mut app, 16);
+
+    assert!(!app.world().get::<Crouch>(character).unwrap().crouching);
+    assert_eq!(collider_heights(&app, character), standing);
+}
+
+#[test]
+fn crouching_at_sprint_speed_slides() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    set_input(&mut app, ScriptedInput { movement: Vec2::Y, sprint: true, ..default() });
+    step(&mut app, 64);
+
+    // Let go of movement, so only the slide keeps the character going.
+    set_input(&mut app, ScriptedInput { crouch: true, ..default() });
+    step(&mut app, 16);
+    assert!(app.world().get::<Slide>(character).unwrap().sliding);
+    let speed =
+        app.world().get::<KinematicCharacterController>(character).unwrap().velocity.length();
+    assert!(speed > 9.0, "slide should keep its momentum, but speed is {speed}");
+
+    // Friction eventually wins and the slide ends.
+    step(&mut app, 128);
+    assert!(!app.world().get::<Slide>(character).unwrap().sliding);
+    assert!(grounded(&app, character));
+}
+
+/// Spawns a character in the air, running along a runnable wall on its right.
+fn wall_running_character(app: &mut App) -> Entity {
+    let wall = spawn_box(app, Transform::from_xyz(0.55, 5.0, -10.0), Vec3::new(0.2, 10.0, 40.0));
+    app.world_mut().entity_mut(wall).insert(WallRunnable);
+    let character = spawn_character(app, Vec3::Y * 5.0);
+    app.world_mut().get_mut::<KinematicCharacterController>(character).unwrap().velocity =
+        Vec3::new(1.0, 0.0, -8.0);
+    set_input(app, ScriptedInput { movement: Vec2::Y, ..default() });
+    character
+}
+
+#[test]
+fn runs_along_runnable_wall() {
+    let mut app = test_app();
+    let character = wall_running_character(&mut app);
+    let start = translation(&app, character);
+
+    step(&mut app, 32);
+
+    assert!(app.world().get::<WallRun>(character).unwrap().running);
+    let fallen = start.y - translation(&app, character).y;
+    assert!(fallen < 1.0, "wall running should slow the fall, but fell {fallen}");
+}
+
+#[test]
+fn wall_jump_pushes_away_from_wall() {
+    let mut app = test_app();
+    let character = wall_running_character(&mut app);
+    step(&mut app, 16);
+    assert!(app.world().get::<WallRun>(character).unwrap().running);
+
+    set_input(&mut app, ScriptedInput { movement: Vec2::Y, jump: true, ..default() });
+    step(&mut app, 8);
+
+    let velocity = app.world().get::<KinematicCharacterController>(character).unwrap().velocity;
+    assert!(velocity.x < -1.0, "wall jump should push away from the wall, velocity is {velocity}");
+    assert!(velocity.y > 0.0);
+    assert!(!app.world().get::<WallRun>(character).unwrap().running);
+}
+
+#[test]
+fn mantles_onto_ledge_out_of_jump_reach() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    // A ledge 1.4 m high, with its face 1 m in front of the character.
+    spawn_box(&mut app, Transform::from_xyz(0.0, 0.7, -3.0), Vec3::new(4.0, 1.4, 4.0));
+
+    set_input(&mut app, ScriptedInput { movement: Vec2::Y, jump: true, ..default() });
+    step(&mut app, 64);
+
+    let translation = translation(&app, character);
+    let feet = translation.y - 0.8;
+    assert!((feet - 1.4).abs() < 0.1, "feet should be on the ledge, but are at {feet}");
+    assert!(
+        translation.z < -1.2,
+        "character should be on top of the ledge, but is at {translation}"
+    );
+    assert!(!app.world().get::<Mantle>(character).unwrap().mantling);
+}
+
+#[test]
+fn dash_bursts_forward_and_uses_a_charge() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+
+    set_input(&mut app, ScriptedInput { dash: true, ..default() });
+    step(&mut app, 16);
+
+    let translation = translation(&app, character);
+    assert!(translation.z < -3.0, "dash should cover ground quickly, but is at {translation}");
+    let dash = app.world().get::<Dash>(character).unwrap();
+    assert_eq!(dash.0.charges, dash.0.max_charges - 1);
+}
+
+#[test]
+fn dash_does_not_pass_through_walls() {
+    let mut app = test_app();
+    spawn_wall(&mut app, 1.0, 0.05);
+    let character = standing_character(&mut app);
+
+    set_input(&mut app, ScriptedInput { dash: true, ..default() });
+    step(&mut app, 32);
+
+    let translation = translation(&app, character);
+    assert!(translation.z > -1.0, "character dashed through the wall to {translation}");
+}
+
+#[test]
+fn climbs_ladder_and_hangs_without_gravity() {
+    let mut app = test_app();
+    let character = standing_character(&mut app);
+    spawn_wall(&mut app, 1.0, 0.2);
+    let ladder = spawn_box(&mut app, Transform::from_xyz(0.0, 3.0, -0.3), Vec3::new(1.0, 6.0, 1.0));
+    app.world_mut().entity_mut(ladder).insert(Ladder);
+    let start = translation(&app, character);
+
+    set_input(&mut app, ScriptedInput { movement: Vec2::Y, ..default() });
+    step(&mut app, 32);
+    let climbed = translation(&app, character);
+    assert!(app.world().get::<Climb>(character).unwrap().climbing);
+    assert!(climbed.y - start.y > 1.0, "character should have climbed, but is at {climbed}");
+
+    set_input(&mut app, ScriptedInput::default());
+    step(&mut app, 32);
+    let hanging = translation(&app, character);
+    assert!((hanging.y - climbed.y).abs() < 0.05, "character slid down the ladder to {hanging}");
+}
+
+#[test]
+fn floats_at_the_surface_of_deep_water() {
+    let mut app = test_app();
+    spawn_floor(&mut app);
+    // A pool whose surface is 3 m above the floor.
+    let water = spawn_box(&mut app, Transform::from_xyz(0.0, 1.5, 0.0), Vec3::new(10.0, 3.0, 10.0));
+    app.world_mut().entity_mut(water).insert(WaterVolume::default());
+    let character = spawn_character(&mut app, Vec3::Y * 4.0);
+
+    step(&mut app, 64 * 4);
+
+    let swim = app.world().get::<Swim>(character).unwrap();
+    assert!(swim.swimming);
+    assert!(
+        (swim.depth - swim.float_depth).abs() < 0.2,
+        "character should float with its feet {} deep, but they are {} deep",
+        swim.float_depth,
+        swim.depth
+    );
+    assert!(!swim.camera_submerged());
+    assert!(!grounded(&app, character));
+}
+
+/// Walks a character across a floor of `material`, then lets go of the input and returns how far
+/// it slid before stopping.
+fn stopping_distance(material: SurfaceMaterial) -> f32 {
+    let mut app = test_app();
+    let floor = spawn_floor(&mut app);
+    app.world_mut().entity_mut(floor).insert(material);
+    let character = spawn_character(&mut app, Vec3::ZERO);
+    step(&mut app, 16);
+
+    set_input(&mut app, ScriptedInput { movement: Vec2::Y, ..default() });
+    step(&mut app, 64);
+    let released = translation(&app, character);
+    set_input(&mut app, ScriptedInput::default());
+    step(&mut app, 128);
+    released.distance(translation(&app, character))
+}
+
+#[test]
+fn ice_is_slippery() {
+    let concrete = stopping_distance(SurfaceMaterial::Concrete);
+    let ice = stopping_distance(SurfaceMaterial::Ice);
+    assert!(ice > concrete * 3.0, "slid {ice} on ice but {concrete} on concrete");
+}
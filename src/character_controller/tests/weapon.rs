@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+
+use super::*;
+use crate::character_controller::{
+    camera_rig::RiggedCamera,
+    surface::{
+        ImpactEffect,
+        SurfaceMaterial,
+    },
+    weapon::{
+        DamageEvent,
+        DamageSource,
+        ImpactEvent,
+        Weapon,
+        WeaponFiredEvent,
+    },
+};
+
+#[test]
+fn shot_damages_target_and_leaves_surface_impact() {
+    let mut app = test_app();
+    let target = spawn_box(&mut app, Transform::from_xyz(0.0, 1.0, -5.0), Vec3::new(2.0, 2.0, 1.0));
+    app.world_mut().entity_mut(target).insert(SurfaceMaterial::Metal);
+    // Aim the camera the character controller spawned at the target.
+    let mut cameras = app.world_mut().query_filtered::<&mut Transform, With<RiggedCamera>>();
+    *cameras.single_mut(app.world_mut()) = Transform::from_xyz(0.0, 1.0, 0.0);
+    let player_id = app.world_mut().spawn_empty().id();
+    let weapon_id = app
+        .world_mut()
+        .spawn(Weapon { damage: 15.0, range: 50.0, fire_interval: 0.1, next_fire: 0.0 })
+        .id();
+    // Let the physics step run, so the spatial query knows about the target.
+    step(&mut app, 2);
+
+    app.world_mut().send_event(WeaponFiredEvent { player_id, weapon_id });
+    step(&mut app, 1);
+
+    let damage: Vec<_> = app
+        .world()
+        .resource::<Events<DamageEvent>>()
+        .iter_current_update_events()
+        .copied()
+        .collect();
+    assert_eq!(damage.len(), 1);
+    assert_eq!(damage[0].target, target);
+    assert_eq!(damage[0].amount, 15.0);
+    assert_eq!(damage[0].source, DamageSource::Weapon { player_id, weapon_id });
+
+    let impacts: Vec<_> = app
+        .world()
+        .resource::<Events<ImpactEvent>>()
+        .iter_current_update_events()
+        .copied()
+        .collect();
+    assert_eq!(impacts.len(), 1);
+    assert_eq!(impacts[0].entity, target);
+    assert_eq!(impacts[0].effect, ImpactEffect::Sparks);
+    assert!((impacts[0].point.z + 4.5).abs() < 0.01, "shot should hit the front of the target");
+    assert!(impacts[0].normal.dot(Vec3::Z) > 0.99);
+}
@@ -1,6 +1,52 @@
 use bevy::prelude::*;
+
+use crate::character_controller::surface::ImpactEffect;
+
 #[derive(Event, Debug)]
 pub struct WeaponFiredEvent {
     pub player_id: Entity,
     pub weapon_id: Entity,
 }
+
+/// Event sent whenever something deals damage to an entity. Weapons and the environment both go
+/// through this event, so health, kill feeds and hit markers only need to listen in one place.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageEvent {
+    /// The entity that takes the damage.
+    pub target: Entity,
+    /// How much damage is dealt.
+    pub amount: f32,
+    /// What dealt the damage, a death from this damage is credited to it.
+    pub source: DamageSource,
+}
+
+/// Event sent wherever a shot hits a surface, so effects can be spawned there.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ImpactEvent {
+    /// The collider that was hit.
+    pub entity: Entity,
+    /// The effect for the material of the surface that was hit.
+    pub effect: ImpactEffect,
+    /// The point the shot hit.
+    pub point: Vec3,
+    /// The normal of the surface at `point`.
+    pub normal: Vec3,
+}
+
+/// What dealt a [`DamageEvent`].
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+pub enum DamageSource {
+    /// A weapon fired by a player.
+    Weapon { player_id: Entity, weapon_id: Entity },
+    /// Landing too hard.
+    Fall,
+}
+
+impl std::fmt::Display for DamageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Weapon { player_id, .. } => write!(f, "{player_id}"),
+            Self::Fall => write!(f, "the fall"),
+        }
+    }
+}
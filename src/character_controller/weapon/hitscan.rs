@@ -0,0 +1,77 @@
+//! Hitscan weapon fire.
+//!
+//! Every [`WeaponFiredEvent`] traces a ray from the camera, out to the weapon's range. The first
+//! body it hits takes the weapon's damage as a [`DamageEvent`] credited to the shooter, and an
+//! [`ImpactEvent`] carries the effect its [`SurfaceMaterial`] leaves behind.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+use super::{
+    super::{
+        camera_rig::RiggedCamera,
+        kinematic_controller::collider_body,
+        surface::SurfaceMaterial,
+    },
+    components::Weapon,
+    event::{
+        DamageEvent,
+        DamageSource,
+        ImpactEvent,
+        WeaponFiredEvent,
+    },
+    input::weapon_input,
+};
+use crate::GameState;
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(Update, fire_hitscan.after(weapon_input).run_if(in_state(GameState::Playing)));
+}
+
+/// Traces the shots of fired weapons, damages whatever they hit and leaves an impact effect there.
+pub fn fire_hitscan(
+    mut fired_events: EventReader<WeaponFiredEvent>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut impact_events: EventWriter<ImpactEvent>,
+    weapons: Query<&Weapon>,
+    materials: Query<&SurfaceMaterial>,
+    camera: Query<&Transform, With<RiggedCamera>>,
+    collider_parents: Query<&ColliderParent>,
+    spatial_query: SpatialQuery,
+) {
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+
+    for fired in fired_events.read() {
+        let Ok(weapon) = weapons.get(fired.weapon_id) else {
+            continue;
+        };
+        // Don't let the shooter hit themselves.
+        let filter = SpatialQueryFilter::default().with_excluded_entities([fired.player_id]);
+        let Some(hit) = spatial_query.cast_ray(
+            camera.translation,
+            camera.forward(),
+            weapon.range,
+            true,
+            &filter,
+        ) else {
+            continue;
+        };
+
+        let target = collider_body(hit.entity, &collider_parents);
+        damage_events.send(DamageEvent {
+            target,
+            amount: weapon.damage,
+            source: DamageSource::Weapon { player_id: fired.player_id, weapon_id: fired.weapon_id },
+        });
+
+        let surface = SurfaceMaterial::of(hit.entity, &materials, &collider_parents);
+        impact_events.send(ImpactEvent {
+            entity: hit.entity,
+            effect: surface.impact_effect(),
+            point: camera.translation + camera.forward() * hit.time_of_impact,
+            normal: hit.normal,
+        });
+    }
+}
@@ -1,10 +1,16 @@
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
 
-use crate::{character_controller::CurrentPlayer, GameState};
+use crate::{
+    character_controller::CurrentPlayer,
+    GameState,
+};
 
 use super::{
-    super::input::PlayerActions,
+    super::{
+        input::PlayerActions,
+        movement::Sprint,
+    },
     components::{
         Weapon,
         WeaponID,
@@ -21,11 +27,15 @@ pub fn weapon_input(
     mut event_writer: EventWriter<WeaponFiredEvent>,
     query: Query<&ActionState<PlayerActions>>,
     weapon_query: Query<Entity, (With<Weapon>, With<WeaponID>)>,
-    player_query: Query<(Entity, &WeaponContainer), With<CurrentPlayer>>,
+    player_query: Query<(Entity, &WeaponContainer, Option<&Sprint>), With<CurrentPlayer>>,
 ) {
-    let Ok((player_id, player_weapons)) = player_query.get_single() else {
+    let Ok((player_id, player_weapons, sprint)) = player_query.get_single() else {
         return;
     };
+    // Weapons can't be fired while sprinting.
+    if sprint.is_some_and(|sprint| sprint.sprinting) {
+        return;
+    }
     let Some(weapon_id) = player_weapons.active_slot else {
         return;
     };
@@ -1,11 +1,40 @@
 use bevy::prelude::*;
 mod components;
 mod event;
+mod hitscan;
 mod input;
 mod prefabs;
+pub use components::Weapon;
+pub use event::{
+    DamageEvent,
+    DamageSource,
+    ImpactEvent,
+    WeaponFiredEvent,
+};
 pub(super) fn plugin(app: &mut App) {
+    app.add_event::<WeaponFiredEvent>();
+    app.add_event::<DamageEvent>();
+    app.add_event::<ImpactEvent>();
     // Temporarily appease clippy.
-    app.add_plugins(input::plugin);
+    app.add_plugins((input::plugin, hitscan::plugin));
+    app.add_systems(Update, (log_damage, log_impacts));
+}
+
+/// Logs every [`DamageEvent`], until there is a health system to take the damage.
+fn log_damage(mut damage_events: EventReader<DamageEvent>) {
+    for damage in damage_events.read() {
+        info!("{} took {} damage from {}.", damage.target, damage.amount, damage.source);
+    }
+}
+
+/// Logs every [`ImpactEvent`], until there are particle effects to spawn for them.
+fn log_impacts(mut impact_events: EventReader<ImpactEvent>) {
+    for impact in impact_events.read() {
+        debug!(
+            "Shot hit {} at {} facing {}, leaving {:?}.",
+            impact.entity, impact.point, impact.normal, impact.effect
+        );
+    }
 }
 
 #[derive(Component, Reflect, Debug)]